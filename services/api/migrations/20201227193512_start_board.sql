@@ -0,0 +1 @@
+ALTER TABLE public.games ADD start_board varchar NOT NULL DEFAULT 'rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1';
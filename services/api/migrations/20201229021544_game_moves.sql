@@ -0,0 +1,13 @@
+CREATE TABLE public.game_moves (
+	game_id uuid NOT NULL,
+	ply int4 NOT NULL,
+	san varchar NOT NULL,
+	uci varchar NOT NULL,
+	fen varchar NOT NULL,
+	user_id uuid NOT NULL,
+	time_spent_ms int8 NOT NULL,
+	created_at timestamp NOT NULL DEFAULT now(),
+	CONSTRAINT game_moves_pk PRIMARY KEY (game_id, ply),
+	CONSTRAINT game_moves_game_fk FOREIGN KEY (game_id) REFERENCES games(id) ON UPDATE CASCADE ON DELETE CASCADE,
+	CONSTRAINT game_moves_user_fk FOREIGN KEY (user_id) REFERENCES users(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+ALTER TABLE public.games ADD time_control varchar NULL;
+ALTER TABLE public.games ADD time_base_ms int8 NULL;
+ALTER TABLE public.games ADD time_increment_ms int8 NULL;
+ALTER TABLE public.games ADD days_per_move int4 NULL;
+ALTER TABLE public.games ADD white_clock_ms int8 NULL;
+ALTER TABLE public.games ADD black_clock_ms int8 NULL;
+ALTER TABLE public.games ADD turn_started_at timestamp NOT NULL DEFAULT (now() at time zone 'utc');
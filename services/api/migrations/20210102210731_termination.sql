@@ -0,0 +1 @@
+ALTER TABLE public.games ADD termination varchar NULL;
@@ -0,0 +1,10 @@
+UPDATE public.games SET termination = CASE result
+	WHEN 'WhiteCheckmates' THEN 'Checkmate'
+	WHEN 'BlackCheckmates' THEN 'Checkmate'
+	WHEN 'WhiteResigns' THEN 'Resignation'
+	WHEN 'BlackResigns' THEN 'Resignation'
+	WHEN 'Stalemate' THEN 'Stalemate'
+	WHEN 'DrawAccepted' THEN 'DrawAgreed'
+	WHEN 'DrawDeclared' THEN 'DrawClaimed'
+END
+WHERE result IS NOT NULL AND termination IS NULL;
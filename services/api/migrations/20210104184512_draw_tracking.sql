@@ -0,0 +1,2 @@
+ALTER TABLE public.games ADD halfmove_clock int4 NOT NULL DEFAULT 0;
+ALTER TABLE public.games ADD position_hashes int8[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,3 @@
+ALTER TABLE public.games ADD draw_offered_by varchar NULL;
+ALTER TABLE public.games ADD white_draw_offer_ply int4 NULL;
+ALTER TABLE public.games ADD black_draw_offer_ply int4 NULL;
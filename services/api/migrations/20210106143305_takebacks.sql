@@ -0,0 +1,7 @@
+ALTER TABLE public.games ADD takeback_requested_by varchar NULL;
+
+ALTER TABLE public.game_moves DROP CONSTRAINT game_moves_pk;
+ALTER TABLE public.game_moves ADD id uuid NOT NULL DEFAULT gen_random_uuid();
+ALTER TABLE public.game_moves ADD CONSTRAINT game_moves_pk PRIMARY KEY (id);
+ALTER TABLE public.game_moves ADD taken_back_at timestamp NULL;
+CREATE UNIQUE INDEX game_moves_game_ply_idx ON public.game_moves (game_id, ply) WHERE taken_back_at IS NULL;
@@ -0,0 +1,25 @@
+CREATE TABLE public.webhooks (
+	id uuid NOT NULL DEFAULT gen_random_uuid(),
+	user_id uuid NOT NULL,
+	url varchar NOT NULL,
+	secret varchar NOT NULL,
+	created_at timestamp NOT NULL DEFAULT now(),
+	CONSTRAINT webhooks_pk PRIMARY KEY (id),
+	CONSTRAINT webhooks_user_fk FOREIGN KEY (user_id) REFERENCES users(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE TABLE public.webhook_deliveries (
+	id uuid NOT NULL DEFAULT gen_random_uuid(),
+	webhook_id uuid NOT NULL,
+	event varchar NOT NULL,
+	payload varchar NOT NULL,
+	attempts int4 NOT NULL DEFAULT 0,
+	next_attempt_at timestamp NOT NULL DEFAULT now(),
+	delivered_at timestamp NULL,
+	last_error varchar NULL,
+	created_at timestamp NOT NULL DEFAULT now(),
+	CONSTRAINT webhook_deliveries_pk PRIMARY KEY (id),
+	CONSTRAINT webhook_deliveries_webhook_fk FOREIGN KEY (webhook_id) REFERENCES webhooks(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE INDEX webhook_deliveries_pending_idx ON public.webhook_deliveries (next_attempt_at) WHERE delivered_at IS NULL;
@@ -0,0 +1,18 @@
+CREATE TABLE public.seeks (
+	id uuid NOT NULL DEFAULT gen_random_uuid(),
+	user_id uuid NOT NULL,
+	color varchar NULL,
+	time_control varchar NULL,
+	time_base_ms int8 NULL,
+	time_increment_ms int8 NULL,
+	days_per_move int4 NULL,
+	rating_min int4 NULL,
+	rating_max int4 NULL,
+	game_id uuid NULL,
+	created_at timestamp NOT NULL DEFAULT now(),
+	CONSTRAINT seeks_pk PRIMARY KEY (id),
+	CONSTRAINT seeks_user_fk FOREIGN KEY (user_id) REFERENCES users(id) ON UPDATE CASCADE ON DELETE CASCADE,
+	CONSTRAINT seeks_game_fk FOREIGN KEY (game_id) REFERENCES games(id) ON UPDATE CASCADE ON DELETE SET NULL
+);
+
+CREATE INDEX seeks_open_idx ON public.seeks (created_at) WHERE game_id IS NULL;
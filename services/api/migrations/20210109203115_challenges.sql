@@ -0,0 +1,22 @@
+CREATE TABLE public.challenges (
+	id uuid NOT NULL DEFAULT gen_random_uuid(),
+	challenger_id uuid NOT NULL,
+	target_id uuid NOT NULL,
+	challenger_color varchar NOT NULL,
+	time_control varchar NULL,
+	time_base_ms int8 NULL,
+	time_increment_ms int8 NULL,
+	days_per_move int4 NULL,
+	status varchar NOT NULL DEFAULT 'Pending',
+	game_id uuid NULL,
+	expires_at timestamp NOT NULL,
+	created_at timestamp NOT NULL DEFAULT now(),
+	modified_at timestamp NOT NULL DEFAULT now(),
+	CONSTRAINT challenges_pk PRIMARY KEY (id),
+	CONSTRAINT challenges_challenger_fk FOREIGN KEY (challenger_id) REFERENCES users(id) ON UPDATE CASCADE ON DELETE CASCADE,
+	CONSTRAINT challenges_target_fk FOREIGN KEY (target_id) REFERENCES users(id) ON UPDATE CASCADE ON DELETE CASCADE,
+	CONSTRAINT challenges_game_fk FOREIGN KEY (game_id) REFERENCES games(id) ON UPDATE CASCADE ON DELETE SET NULL
+);
+
+CREATE INDEX challenges_target_idx ON public.challenges (target_id) WHERE status = 'Pending';
+CREATE INDEX challenges_challenger_idx ON public.challenges (challenger_id) WHERE status = 'Pending';
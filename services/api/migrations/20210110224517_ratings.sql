@@ -0,0 +1,31 @@
+ALTER TABLE public.games ADD rated bool NOT NULL DEFAULT true;
+ALTER TABLE public.seeks ADD rated bool NOT NULL DEFAULT true;
+ALTER TABLE public.challenges ADD rated bool NOT NULL DEFAULT true;
+
+CREATE TABLE public.ratings (
+	user_id uuid NOT NULL,
+	category varchar NOT NULL,
+	rating float8 NOT NULL,
+	deviation float8 NOT NULL,
+	volatility float8 NOT NULL,
+	games int4 NOT NULL DEFAULT 0,
+	modified_at timestamp NOT NULL DEFAULT now(),
+	CONSTRAINT ratings_pk PRIMARY KEY (user_id, category),
+	CONSTRAINT ratings_user_fk FOREIGN KEY (user_id) REFERENCES users(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE TABLE public.rating_history (
+	id uuid NOT NULL DEFAULT gen_random_uuid(),
+	user_id uuid NOT NULL,
+	category varchar NOT NULL,
+	game_id uuid NOT NULL,
+	rating float8 NOT NULL,
+	deviation float8 NOT NULL,
+	volatility float8 NOT NULL,
+	created_at timestamp NOT NULL DEFAULT now(),
+	CONSTRAINT rating_history_pk PRIMARY KEY (id),
+	CONSTRAINT rating_history_user_fk FOREIGN KEY (user_id) REFERENCES users(id) ON UPDATE CASCADE ON DELETE CASCADE,
+	CONSTRAINT rating_history_game_fk FOREIGN KEY (game_id) REFERENCES games(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE INDEX rating_history_user_idx ON public.rating_history (user_id, category, created_at);
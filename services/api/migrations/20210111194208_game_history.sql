@@ -0,0 +1,2 @@
+CREATE INDEX games_white_history_idx ON public.games (white_id, modified_at DESC, id DESC);
+CREATE INDEX games_black_history_idx ON public.games (black_id, modified_at DESC, id DESC);
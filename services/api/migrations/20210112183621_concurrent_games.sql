@@ -0,0 +1,4 @@
+ALTER TABLE public.users ADD max_games int4 NOT NULL DEFAULT 1;
+ALTER TABLE public.users ADD CONSTRAINT users_max_games_check CHECK (max_games > 0);
+ALTER TABLE public.users ADD default_game_id uuid NULL;
+ALTER TABLE public.users ADD CONSTRAINT users_default_game_fk FOREIGN KEY (default_game_id) REFERENCES games(id) ON UPDATE CASCADE ON DELETE SET NULL;
@@ -0,0 +1,127 @@
+use crate::{
+	hub::GameEventKind,
+	models::{
+		clock::timeout_result,
+		db,
+		game::{Game, Termination},
+		rating,
+	},
+	webhooks, State,
+};
+use async_std::task;
+use chess::{Color, GameResult};
+use chrono::Utc;
+use sqlx::types::Uuid;
+use std::{convert::TryInto, time::Duration};
+use tide::log;
+
+/// Periodically ends games whose side to move has run out of time or stopped playing.
+#[derive(Debug, Clone)]
+pub struct Adjudicator {
+	pub state: State,
+	pub interval: Duration,
+	/// How long an untimed game may go without a move before the side to move forfeits.
+	pub abandon_after: chrono::Duration,
+}
+
+impl Adjudicator {
+	pub async fn run(self) {
+		loop {
+			if let Err(e) = self.adjudicate_all().await {
+				log::error!("adjudication failed: {}", e);
+			}
+
+			task::sleep(self.interval).await;
+		}
+	}
+
+	async fn adjudicate_all(&self) -> tide::Result<()> {
+		let now = Utc::now().naive_utc();
+		let games = sqlx::query!(
+			"select id from games
+where result is null and (
+	(time_control is not null and turn_started_at + (case when split_part(board, ' ', 2) = 'w' then white_clock_ms else black_clock_ms end) * interval '1 millisecond' < $1)
+	or (time_control is null and turn_started_at < $2)
+)",
+			now,
+			now - self.abandon_after
+		)
+		.fetch_all(&self.state.db)
+		.await?;
+
+		// one game that can't be loaded, such as a corrupt one, mustn't hold up the rest
+		for game in games {
+			if let Err(e) = self.adjudicate(&game.id).await {
+				log::error!("failed to adjudicate game {}: {}", game.id, e);
+			}
+		}
+
+		Ok(())
+	}
+
+	async fn adjudicate(&self, game_id: &Uuid) -> tide::Result<()> {
+		let mut txn = self.state.db.begin().await?;
+
+		let game = match sqlx::query_as!(
+			db::Game,
+			"select * from games where id = $1 and result is null for update",
+			game_id
+		)
+		.fetch_optional(&mut txn)
+		.await?
+		{
+			Some(game) => game,
+			None => return Ok(()),
+		};
+
+		let turn_started_at = game.turn_started_at;
+		let mut game: Game = game.try_into()?;
+
+		let now = Utc::now().naive_utc();
+		let side_to_move = game.side_to_move;
+		let position = game.board.current_position();
+
+		let (result, termination) = match &mut game.clock {
+			Some(clock) if clock.remaining(side_to_move, now) <= 0 => {
+				clock.punch(side_to_move, now);
+				(timeout_result(&position, side_to_move), Termination::Timeout)
+			}
+			None if now - turn_started_at > self.abandon_after => {
+				let result = match side_to_move {
+					Color::White => GameResult::WhiteResigns,
+					Color::Black => GameResult::BlackResigns,
+				};
+				(result, Termination::Abandoned)
+			}
+			// a move was made since the game was found
+			_ => return Ok(()),
+		};
+
+		game.result = Some(result);
+		game.termination = Some(termination);
+		game.pgn = (&game.to_pgn()?).into();
+
+		let result: &str = result.into();
+		let termination: &str = termination.into();
+		sqlx::query!(
+			"update games set result = $1, termination = $2, white_clock_ms = $3, black_clock_ms = $4, modified_at = now() where id = $5",
+			result,
+			termination,
+			game.clock.as_ref().map(|clock| clock.white_ms),
+			game.clock.as_ref().map(|clock| clock.black_ms),
+			game.id
+		)
+		.execute(&mut txn)
+		.await?;
+
+		rating::rate_game(&game, &mut txn).await?;
+
+		let game = game.with_users(&self.state.db).await?;
+		webhooks::enqueue(&game, &[GameEventKind::End], &mut txn).await?;
+		txn.commit().await?;
+
+		log::info!("adjudicated game {}: {} ({})", game.id, result, termination);
+		self.state.publish(&game, &[GameEventKind::End]).await;
+		Ok(())
+	}
+}
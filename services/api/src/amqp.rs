@@ -0,0 +1,116 @@
+use crate::{hub::GameEventKind, models::game::GameWithUsers};
+use async_amqp::LapinAsyncStdExt;
+use async_channel::{Receiver, Sender};
+use async_std::{future::timeout, task};
+use lapin::{
+	options::{BasicPublishOptions, ExchangeDeclareOptions},
+	types::FieldTable,
+	BasicProperties, Channel, Connection, ConnectionProperties, ExchangeKind,
+};
+use std::time::Duration;
+use tide::log;
+
+/// Other services bind to this exchange with the event names as routing keys.
+const EXCHANGE: &str = "api";
+/// Messages waiting to be published beyond this are dropped, so a slow or missing broker can't back up requests.
+const QUEUE_SIZE: usize = 256;
+/// How long connecting or publishing may take before the broker is treated as unavailable.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+struct Message {
+	routing_key: &'static str,
+	payload: Vec<u8>,
+}
+
+/// Publishes game events to RabbitMQ from a background task, so requests never wait for the broker.
+#[derive(Debug, Clone)]
+pub struct Publisher {
+	queue: Sender<Message>,
+}
+
+impl Publisher {
+	/// Starts publishing to the broker at `url`, which is connected to when first needed and again whenever
+	/// publishing fails.
+	pub fn start(url: String) -> Self {
+		let (queue, messages) = async_channel::bounded(QUEUE_SIZE);
+		task::spawn(run(url, messages));
+		Self { queue }
+	}
+
+	/// Queues the events to be published, dropping them if the broker has fallen too far behind.
+	pub fn publish(&self, game: &GameWithUsers, kinds: &[GameEventKind]) -> serde_json::Result<()> {
+		let payload = serde_json::to_vec(game)?;
+
+		for routing_key in kinds.iter().filter_map(GameEventKind::topic) {
+			let message = Message {
+				routing_key,
+				payload: payload.clone(),
+			};
+			if self.queue.try_send(message).is_err() {
+				log::warn!("AMQP queue is full, dropping {} for game {}", routing_key, game.id);
+			}
+		}
+
+		Ok(())
+	}
+}
+
+async fn open(url: &str) -> lapin::Result<Channel> {
+	let connection = Connection::connect(url, ConnectionProperties::default().with_async_std()).await?;
+	let channel = connection.create_channel().await?;
+	channel
+		.exchange_declare(
+			EXCHANGE,
+			ExchangeKind::Direct,
+			ExchangeDeclareOptions::default(),
+			FieldTable::default(),
+		)
+		.await?;
+
+	Ok(channel)
+}
+
+/// Publishes `message`, first opening a new channel if there isn't an open one.
+async fn send(url: &str, channel: &mut Option<Channel>, message: &Message) -> tide::Result<()> {
+	let open_channel = match channel {
+		Some(open_channel) if open_channel.status().connected() => open_channel.clone(),
+		_ => {
+			let open_channel = timeout(TIMEOUT, open(url)).await??;
+			*channel = Some(open_channel.clone());
+			open_channel
+		}
+	};
+
+	timeout(
+		TIMEOUT,
+		open_channel.basic_publish(
+			EXCHANGE,
+			message.routing_key,
+			BasicPublishOptions::default(),
+			message.payload.clone(),
+			BasicProperties::default().with_content_type("application/json".into()),
+		),
+	)
+	.await??;
+
+	Ok(())
+}
+
+/// Publishes queued messages for as long as the API runs.
+async fn run(url: String, messages: Receiver<Message>) {
+	let mut channel = None;
+
+	while let Ok(message) = messages.recv().await {
+		// a dropped connection may only show up as a failed publish, so there's one more try on a new channel
+		for attempt in 1..=2 {
+			match send(&url, &mut channel, &message).await {
+				Ok(()) => break,
+				Err(e) => {
+					log::warn!("failed to publish {} to AMQP (attempt {}): {}", message.routing_key, attempt, e);
+					channel = None;
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,38 @@
+use crate::models::{
+	db,
+	game::{Game, GameError},
+};
+use async_std::prelude::*;
+use sqlx::PgPool;
+use std::convert::TryFrom;
+use tide::log;
+
+/// Logs every unfinished game whose stored position can't be reproduced by replaying its moves from the start position.
+/// Finished games are left alone since nothing changes them any more, and checking them all would slow down every start.
+pub async fn report_corrupt_games(db: PgPool) {
+	let mut games = sqlx::query_as!(db::Game, "select * from games where result is null").fetch(&db);
+	let mut corrupt = 0;
+
+	while let Some(game) = games.next().await {
+		let game = match game {
+			Ok(game) => game,
+			Err(e) => {
+				log::error!("failed to check games for corruption: {}", e);
+				return;
+			}
+		};
+
+		match Game::try_from(game) {
+			Ok(_) => {}
+			Err(e @ GameError::Corrupt { .. }) => {
+				log::error!("{}", e);
+				corrupt += 1;
+			}
+			Err(e) => log::error!("failed to load game: {}", e),
+		}
+	}
+
+	if corrupt > 0 {
+		log::warn!("found {} corrupt games", corrupt);
+	}
+}
@@ -0,0 +1,90 @@
+use crate::models::game::GameWithUsers;
+use async_channel::{Receiver, Sender};
+use sqlx::types::Uuid;
+use std::{
+	collections::HashMap,
+	sync::{Arc, Mutex},
+};
+use strum::IntoStaticStr;
+
+/// Events a subscriber can receive beyond the buffer before they're disconnected.
+const SUBSCRIBER_BUFFER: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoStaticStr)]
+#[strum(serialize_all = "kebab_case")]
+pub enum GameEventKind {
+	Created,
+	Move,
+	DrawOffer,
+	DrawAccepted,
+	DrawDeclined,
+	DrawClaimed,
+	Resign,
+	TakebackRequest,
+	Takeback,
+	TakebackDeclined,
+	End,
+}
+
+impl GameEventKind {
+	/// The name the event is published under to services outside the API, if it's published at all.
+	pub fn topic(&self) -> Option<&'static str> {
+		match self {
+			GameEventKind::Created => Some("game.created"),
+			GameEventKind::Move => Some("game.move"),
+			GameEventKind::DrawOffer => Some("game.draw_offer"),
+			GameEventKind::End => Some("game.ended"),
+			_ => None,
+		}
+	}
+}
+
+/// Something that happened in a game, along with the game as it was afterwards serialized to JSON.
+#[derive(Debug, Clone)]
+pub struct GameEvent {
+	pub kind: GameEventKind,
+	pub data: Arc<str>,
+}
+
+/// Fans game events out to everyone subscribed to the game.
+#[derive(Debug, Clone, Default)]
+pub struct Hub {
+	subscribers: Arc<Mutex<HashMap<Uuid, Vec<Sender<GameEvent>>>>>,
+}
+
+impl Hub {
+	pub fn subscribe(&self, game_id: Uuid) -> Receiver<GameEvent> {
+		let (sender, receiver) = async_channel::bounded(SUBSCRIBER_BUFFER);
+		self.subscribers
+			.lock()
+			.unwrap()
+			.entry(game_id)
+			.or_default()
+			.push(sender);
+
+		receiver
+	}
+
+	/// Sends each event to every subscriber of the game, dropping those that have gone away or fallen behind.
+	/// Subscribers are disconnected once the game ends.
+	pub fn publish(&self, game: &GameWithUsers, kinds: &[GameEventKind]) -> serde_json::Result<()> {
+		let data: Arc<str> = serde_json::to_string(game)?.into();
+
+		let mut subscribers = self.subscribers.lock().unwrap();
+		if let Some(senders) = subscribers.get_mut(&game.id) {
+			for kind in kinds {
+				let event = GameEvent {
+					kind: *kind,
+					data: data.clone(),
+				};
+				senders.retain(|sender| sender.try_send(event.clone()).is_ok());
+			}
+
+			if senders.is_empty() || kinds.contains(&GameEventKind::End) {
+				subscribers.remove(&game.id);
+			}
+		}
+
+		Ok(())
+	}
+}
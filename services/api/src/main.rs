@@ -1,9 +1,16 @@
+mod adjudicator;
+mod amqp;
+mod audit;
+mod hub;
 mod middleware;
 mod models;
+mod pgn;
 mod routes;
 mod serde;
 mod state;
+mod webhooks;
 
+use models::r#move::{ResolveError, SANError};
 use sqlx::postgres::PgPoolOptions;
 pub use state::State;
 use std::{env, time::Duration};
@@ -24,22 +31,142 @@ async fn main() -> tide::Result<()> {
 
 	MIGRATOR.run(&pool).await?;
 
-	let state = State { db: pool };
+	let state = State {
+		db: pool,
+		broadcast_delay_plies: env::var("BROADCAST_DELAY_PLIES")
+			.ok()
+			.and_then(|plies| plies.parse().ok())
+			.unwrap_or(0),
+		challenge_expiry: Duration::from_secs(
+			env::var("CHALLENGE_EXPIRY_SECS")
+				.ok()
+				.and_then(|secs| secs.parse().ok())
+				.unwrap_or(24 * 60 * 60),
+		),
+		hub: hub::Hub::default(),
+		// the broker is optional and only connected to in the background, so the API starts without it
+		amqp: env::var("AMQP_URL").ok().map(amqp::Publisher::start),
+	};
+
+	let adjudicator = adjudicator::Adjudicator {
+		state: state.clone(),
+		interval: Duration::from_secs(
+			env::var("ADJUDICATION_INTERVAL_SECS")
+				.ok()
+				.and_then(|secs| secs.parse().ok())
+				.unwrap_or(30),
+		),
+		abandon_after: chrono::Duration::hours(
+			env::var("ABANDON_AFTER_HOURS")
+				.ok()
+				.and_then(|hours| hours.parse().ok())
+				.unwrap_or(72),
+		),
+	};
+	async_std::task::spawn(adjudicator.run());
+
+	let deliverer = webhooks::Deliverer {
+		db: state.db.clone(),
+		interval: Duration::from_secs(
+			env::var("WEBHOOK_INTERVAL_SECS")
+				.ok()
+				.and_then(|secs| secs.parse().ok())
+				.unwrap_or(10),
+		),
+		timeout: Duration::from_secs(
+			env::var("WEBHOOK_TIMEOUT_SECS")
+				.ok()
+				.and_then(|secs| secs.parse().ok())
+				.unwrap_or(10),
+		),
+	};
+	async_std::task::spawn(deliverer.run());
+	async_std::task::spawn(audit::report_corrupt_games(state.db.clone()));
 
 	let mut app = tide::with_state(state);
 
-	app.at("/games")
+	// tell clients why a move couldn't be read or played, since there's no other way for them to find out
+	app.with(tide::utils::After(|mut res: tide::Response| async move {
+		let message = res
+			.downcast_error::<SANError>()
+			.map(ToString::to_string)
+			.or_else(|| res.downcast_error::<ResolveError>().map(ToString::to_string));
+		if let Some(message) = message {
+			res.set_body(message);
+		}
+
+		Ok(res)
+	}));
+
+	app.at("/games/active")
+		.with(middleware::user::get_user)
+		.get(routes::games::list_active_games);
+	app.at("/games/import")
 		.with(middleware::user::get_user)
-		.post(routes::games::create_game);
+		.post(routes::games::import::import_game);
 	app.at("/games/:game_id")
 		.with(middleware::user::get_user)
 		.with(middleware::game::get_game)
 		.get(routes::games::get_game);
+	app.at("/games/:game_id/pgn")
+		.with(middleware::user::get_user)
+		.with(middleware::game::get_game)
+		.get(routes::games::get_pgn);
 	app.at("/games/:game_id/moves")
 		.with(middleware::user::get_user)
 		.with(middleware::game::get_game)
 		.with(middleware::color::get_user_color)
+		.get(routes::games::moves::list_moves)
 		.put(routes::games::moves::make_move);
+	app.at("/games/:game_id/events")
+		.with(middleware::user::get_user)
+		.with(middleware::game::get_game)
+		.with(middleware::color::get_user_color)
+		.get(tide::sse::endpoint(routes::games::events::stream_events));
+	app.at("/challenges")
+		.with(middleware::user::get_user)
+		.get(routes::games::challenges::list_challenges)
+		.post(routes::games::challenges::create_challenge);
+	app.at("/challenges/:challenge_id")
+		.with(middleware::user::get_user)
+		.delete(routes::games::challenges::cancel_challenge);
+	app.at("/challenges/:challenge_id/accept")
+		.with(middleware::user::get_user)
+		.post(routes::games::challenges::accept_challenge);
+	app.at("/challenges/:challenge_id/decline")
+		.with(middleware::user::get_user)
+		.post(routes::games::challenges::decline_challenge);
+	app.at("/seeks")
+		.with(middleware::user::get_user)
+		.get(routes::games::seeks::list_seeks)
+		.post(routes::games::seeks::create_seek);
+	app.at("/seeks/:seek_id")
+		.with(middleware::user::get_user)
+		.delete(routes::games::seeks::cancel_seek);
+	app.at("/seeks/:seek_id/accept")
+		.with(middleware::user::get_user)
+		.post(routes::games::seeks::accept_seek);
+	app.at("/settings")
+		.with(middleware::user::get_user)
+		.get(routes::users::get_settings)
+		.patch(routes::users::update_settings);
+	app.at("/users/:user_id/games")
+		.with(middleware::user::get_user)
+		.get(routes::users::list_games);
+	app.at("/users/:user_id/stats")
+		.with(middleware::user::get_user)
+		.get(routes::users::get_stats);
+	app.at("/leaderboard")
+		.with(middleware::user::get_user)
+		.get(routes::users::get_leaderboard);
+	app.at("/webhooks")
+		.with(middleware::user::get_user)
+		.get(routes::webhooks::list_webhooks)
+		.post(routes::webhooks::create_webhook);
+	app.at("/webhooks/:webhook_id")
+		.with(middleware::user::get_user)
+		.delete(routes::webhooks::delete_webhook);
+	app.at("/public/games/:game_id").get(routes::games::public::get_game);
 
 	app.listen("0.0.0.0:8080").await?;
 	Ok(())
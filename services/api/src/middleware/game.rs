@@ -14,33 +14,47 @@ pub fn get_game<'a>(
 		let user = req.ext::<User>().unwrap();
 		let game_id = req.param("game_id")?;
 		let pool = &req.state().db;
-		if game_id == "current" {
-			let mut games = sqlx::query_as!(
-				db::Game,
-				r#"select games.id, games.white_id, games.black_id, games.board, games.moves, games.result, games.created_at, games.modified_at
-from games
-left join users on users.id = games.black_id
-	or users.id = games.white_id
-where users.id = $1 and games.result is null
-limit 2"#,
-				user.id
-			)
-			.fetch_all(pool)
-			.await?;
+		if game_id == "default" {
+			// the chosen default game, or else the only game in progress
+			let mut games = match user.default_game_id {
+				Some(default_game_id) => sqlx::query_as!(
+					db::Game,
+					"select * from games where id = $1 and result is null",
+					default_game_id
+				)
+				.fetch_all(pool)
+				.await?,
+				None => vec![],
+			};
+
+			if games.is_empty() {
+				games = sqlx::query_as!(
+					db::Game,
+					"select * from games where (white_id = $1 or black_id = $1) and result is null limit 2",
+					user.id
+				)
+				.fetch_all(pool)
+				.await?;
+			}
 
-			if games.len() == 1 {
-				req.set_ext::<Game>(games.remove(0).try_into()?);
-				Ok(next.run(req).await)
-			} else {
-				Err(tide::Error::from_str(
-					StatusCode::BadRequest,
-					"multiple games in progress",
-				))
+			match games.len() {
+				0 => Err(tide::Error::from_str(StatusCode::NotFound, "no games in progress")),
+				1 => {
+					req.set_ext::<Game>(games.remove(0).try_into()?);
+					Ok(next.run(req).await)
+				}
+				_ => Err(tide::Error::from_str(
+					StatusCode::Conflict,
+					"multiple games in progress; choose a default game",
+				)),
 			}
 		} else if game_id == "previous" {
 			let maybe_game = sqlx::query_as!(
 				db::Game,
-				r#"select games.id, games.white_id, games.black_id, games.board, games.moves, games.result, games.created_at, games.modified_at
+				r#"select games.id, games.white_id, games.black_id, games.board, games.moves, games.result, games.created_at, games.modified_at, games.start_board,
+	games.time_control, games.time_base_ms, games.time_increment_ms, games.days_per_move, games.white_clock_ms, games.black_clock_ms, games.turn_started_at, games.termination,
+	games.halfmove_clock, games.position_hashes, games.draw_offered_by, games.white_draw_offer_ply, games.black_draw_offer_ply,
+	games.takeback_requested_by, games.rated
 from games
 left join users on users.id = games.black_id
 	or users.id = games.white_id
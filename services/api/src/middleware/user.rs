@@ -39,6 +39,8 @@ pub fn get_user<'a>(
 					id: user.id.unwrap(),
 					created_at: user.created_at.unwrap(),
 					modified_at: user.modified_at.unwrap(),
+					max_games: user.max_games.unwrap(),
+					default_game_id: user.default_game_id,
 				}
 			}
 			None => {
@@ -1,4 +1,12 @@
+pub mod challenge;
+pub mod clock;
 pub mod db;
+pub mod draw;
 pub mod game;
+pub mod history;
 pub mod r#move;
+pub mod rating;
+pub mod seek;
+pub mod stats;
 pub mod user;
+pub mod webhook;
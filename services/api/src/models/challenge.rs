@@ -0,0 +1,66 @@
+use super::{clock::TimeControl, db, game::color_from_column};
+use chess::Color;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use strum::{EnumString, IntoStaticStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, IntoStaticStr, EnumString)]
+pub enum ChallengeStatus {
+	Pending,
+	Accepted,
+	Declined,
+	Cancelled,
+	Expired,
+}
+
+/// An invitation for a particular user to play a game, which only starts once they accept it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Challenge {
+	#[serde(with = "crate::serde::uuid")]
+	pub id: Uuid,
+	#[serde(with = "crate::serde::uuid")]
+	pub challenger_id: Uuid,
+	#[serde(with = "crate::serde::uuid")]
+	pub target_id: Uuid,
+	pub challenger_color: Color,
+	pub time_control: Option<TimeControl>,
+	pub rated: bool,
+	pub status: ChallengeStatus,
+	#[serde(with = "crate::serde::option_uuid")]
+	pub game_id: Option<Uuid>,
+	pub expires_at: NaiveDateTime,
+	pub created_at: NaiveDateTime,
+}
+
+impl Challenge {
+	/// The `(white_id, black_id)` pair for the challenged game.
+	pub fn players(&self) -> (Uuid, Uuid) {
+		match self.challenger_color {
+			Color::White => (self.challenger_id, self.target_id),
+			Color::Black => (self.target_id, self.challenger_id),
+		}
+	}
+}
+
+impl From<db::Challenge> for Challenge {
+	fn from(challenge: db::Challenge) -> Self {
+		Self {
+			id: challenge.id,
+			challenger_id: challenge.challenger_id,
+			target_id: challenge.target_id,
+			challenger_color: color_from_column(&challenge.challenger_color).unwrap_or(Color::White),
+			time_control: TimeControl::from_columns(
+				challenge.time_control.as_deref(),
+				challenge.time_base_ms,
+				challenge.time_increment_ms,
+				challenge.days_per_move,
+			),
+			rated: challenge.rated,
+			status: challenge.status.parse().unwrap_or(ChallengeStatus::Pending),
+			game_id: challenge.game_id,
+			expires_at: challenge.expires_at,
+			created_at: challenge.created_at,
+		}
+	}
+}
@@ -0,0 +1,186 @@
+use chess::{Board, Color, GameResult, Piece};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+const MS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum TimeControl {
+	/// Each player starts with `base_ms` and gains `increment_ms` after every move.
+	Fischer { base_ms: i64, increment_ms: i64 },
+	/// Each player starts with `base_ms` and gets back up to `delay_ms` of the time spent on every move.
+	Bronstein { base_ms: i64, delay_ms: i64 },
+	/// Each move must be made within `days_per_move` days.
+	Correspondence { days_per_move: i32 },
+}
+
+impl TimeControl {
+	pub fn initial_ms(&self) -> i64 {
+		match *self {
+			TimeControl::Fischer { base_ms, .. } | TimeControl::Bronstein { base_ms, .. } => base_ms,
+			TimeControl::Correspondence { days_per_move } => days_per_move as i64 * MS_PER_DAY,
+		}
+	}
+
+	/// Splits the time control into the `time_control`, `time_base_ms`, `time_increment_ms` and `days_per_move` columns.
+	pub fn to_columns(&self) -> (&'static str, Option<i64>, Option<i64>, Option<i32>) {
+		match *self {
+			TimeControl::Fischer { base_ms, increment_ms } => ("Fischer", Some(base_ms), Some(increment_ms), None),
+			TimeControl::Bronstein { base_ms, delay_ms } => ("Bronstein", Some(base_ms), Some(delay_ms), None),
+			TimeControl::Correspondence { days_per_move } => ("Correspondence", None, None, Some(days_per_move)),
+		}
+	}
+
+	pub fn from_columns(
+		time_control: Option<&str>,
+		base_ms: Option<i64>,
+		increment_ms: Option<i64>,
+		days_per_move: Option<i32>,
+	) -> Option<Self> {
+		match time_control? {
+			"Fischer" => Some(TimeControl::Fischer {
+				base_ms: base_ms?,
+				increment_ms: increment_ms.unwrap_or_default(),
+			}),
+			"Bronstein" => Some(TimeControl::Bronstein {
+				base_ms: base_ms?,
+				delay_ms: increment_ms.unwrap_or_default(),
+			}),
+			"Correspondence" => Some(TimeControl::Correspondence {
+				days_per_move: days_per_move?,
+			}),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Clock {
+	pub time_control: TimeControl,
+	pub white_ms: i64,
+	pub black_ms: i64,
+	pub turn_started_at: NaiveDateTime,
+}
+
+impl Clock {
+	/// The time `color` had when the current turn started.
+	fn clock_of(&mut self, color: Color) -> &mut i64 {
+		match color {
+			Color::White => &mut self.white_ms,
+			Color::Black => &mut self.black_ms,
+		}
+	}
+
+	/// The time the side to move has left at `now`.
+	pub fn remaining(&self, side_to_move: Color, now: NaiveDateTime) -> i64 {
+		let elapsed = (now - self.turn_started_at).num_milliseconds().max(0);
+		let started_with = match side_to_move {
+			Color::White => self.white_ms,
+			Color::Black => self.black_ms,
+		};
+
+		started_with - elapsed
+	}
+
+	/// Charges the side to move for its turn and starts the opponent's. Returns false if their flag fell first.
+	pub fn punch(&mut self, side_to_move: Color, now: NaiveDateTime) -> bool {
+		let remaining = self.remaining(side_to_move, now);
+		if remaining <= 0 {
+			*self.clock_of(side_to_move) = 0;
+			return false;
+		}
+
+		let elapsed = (now - self.turn_started_at).num_milliseconds().max(0);
+		let time_control = self.time_control;
+		*self.clock_of(side_to_move) = match time_control {
+			TimeControl::Fischer { increment_ms, .. } => remaining + increment_ms,
+			TimeControl::Bronstein { delay_ms, .. } => remaining + elapsed.min(delay_ms),
+			TimeControl::Correspondence { .. } => time_control.initial_ms(),
+		};
+
+		self.turn_started_at = now;
+		true
+	}
+}
+
+/// Whether `color` has the material to checkmate by any sequence of legal moves.
+pub fn can_checkmate(board: &Board, color: Color) -> bool {
+	let ours = *board.color_combined(color);
+	let theirs = *board.color_combined(!color);
+
+	let heavy = *board.pieces(Piece::Pawn) | *board.pieces(Piece::Rook) | *board.pieces(Piece::Queen);
+	if (heavy & ours).popcnt() > 0 {
+		return true;
+	}
+
+	let minors = (*board.pieces(Piece::Knight) | *board.pieces(Piece::Bishop)) & ours;
+	match minors.popcnt() {
+		0 => false,
+		// a lone knight can only mate if the opponent has something to block their king in with
+		1 if (minors & *board.pieces(Piece::Knight)).popcnt() > 0 => theirs.popcnt() > 1,
+		_ => {
+			// bishops confined to one square colour can't mate unless the opponent has a piece that can be captured or block
+			let knights = (*board.pieces(Piece::Knight) & (ours | theirs)).popcnt();
+			let bishop_colors = (*board.pieces(Piece::Bishop) & (ours | theirs))
+				.map(|square| (square.get_rank().to_index() + square.get_file().to_index()) % 2)
+				.collect::<Vec<_>>();
+
+			knights > 0
+				|| (theirs & !*board.pieces(Piece::Bishop) & !*board.pieces(Piece::King)).popcnt() > 0
+				|| bishop_colors.windows(2).any(|pair| pair[0] != pair[1])
+		}
+	}
+}
+
+/// The result when `flagged` runs out of time: a loss, unless their opponent couldn't possibly checkmate them.
+/// [`GameResult`] can't say that a flag fell, so the game's [`Termination::Timeout`] is what records it.
+///
+/// [`Termination::Timeout`]: crate::models::game::Termination::Timeout
+pub fn timeout_result(board: &Board, flagged: Color) -> GameResult {
+	if !can_checkmate(board, !flagged) {
+		return GameResult::DrawDeclared;
+	}
+
+	match flagged {
+		Color::White => GameResult::WhiteResigns,
+		Color::Black => GameResult::BlackResigns,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::str::FromStr;
+
+	fn can_white_checkmate(fen: &str) -> bool {
+		can_checkmate(&Board::from_str(fen).unwrap(), Color::White)
+	}
+
+	#[test]
+	fn lone_bishop_cant_mate_bare_king() {
+		assert!(!can_white_checkmate("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"));
+	}
+
+	#[test]
+	fn bishops_on_the_same_square_colour_cant_mate() {
+		assert!(!can_white_checkmate("4k3/8/8/8/8/4b3/8/2B1K3 w - - 0 1"));
+	}
+
+	#[test]
+	fn bishops_on_opposite_square_colours_can_mate() {
+		assert!(can_white_checkmate("4k3/8/8/8/4b3/8/8/2B1K3 w - - 0 1"));
+	}
+
+	#[test]
+	fn lone_knight_needs_a_blocker() {
+		assert!(!can_white_checkmate("4k3/8/8/8/8/8/8/2N1K3 w - - 0 1"));
+		assert!(can_white_checkmate("4k3/8/8/8/8/4b3/8/2N1K3 w - - 0 1"));
+	}
+
+	#[test]
+	fn same_coloured_bishops_draw_on_time() {
+		let board = Board::from_str("4k3/8/8/8/8/4b3/8/2B1K3 w - - 0 1").unwrap();
+		assert_eq!(timeout_result(&board, Color::Black), GameResult::DrawDeclared);
+	}
+}
@@ -1,2 +1,6 @@
+pub mod challenge;
 pub mod game;
-pub use game::Game;
+pub mod seek;
+pub use challenge::Challenge;
+pub use game::{Game, NewGame};
+pub use seek::Seek;
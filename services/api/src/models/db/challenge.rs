@@ -0,0 +1,20 @@
+use chrono::NaiveDateTime;
+use sqlx::{types::Uuid, FromRow};
+
+#[derive(Debug, Clone, FromRow)]
+pub struct Challenge {
+	pub id: Uuid,
+	pub challenger_id: Uuid,
+	pub target_id: Uuid,
+	pub challenger_color: String,
+	pub time_control: Option<String>,
+	pub time_base_ms: Option<i64>,
+	pub time_increment_ms: Option<i64>,
+	pub days_per_move: Option<i32>,
+	pub status: String,
+	pub game_id: Option<Uuid>,
+	pub expires_at: NaiveDateTime,
+	pub created_at: NaiveDateTime,
+	pub modified_at: NaiveDateTime,
+	pub rated: bool,
+}
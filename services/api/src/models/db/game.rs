@@ -1,3 +1,4 @@
+use crate::models::{clock::TimeControl, draw::position_hash};
 use chrono::NaiveDateTime;
 use sqlx::{types::Uuid, FromRow};
 
@@ -11,4 +12,92 @@ pub struct Game {
 	pub result: Option<String>,
 	pub created_at: NaiveDateTime,
 	pub modified_at: NaiveDateTime,
+	pub start_board: String,
+	pub time_control: Option<String>,
+	pub time_base_ms: Option<i64>,
+	pub time_increment_ms: Option<i64>,
+	pub days_per_move: Option<i32>,
+	pub white_clock_ms: Option<i64>,
+	pub black_clock_ms: Option<i64>,
+	pub turn_started_at: NaiveDateTime,
+	pub termination: Option<String>,
+	pub halfmove_clock: i32,
+	pub position_hashes: Vec<i64>,
+	pub draw_offered_by: Option<String>,
+	pub white_draw_offer_ply: Option<i32>,
+	pub black_draw_offer_ply: Option<i32>,
+	pub takeback_requested_by: Option<String>,
+	pub rated: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewGame {
+	pub white_id: Uuid,
+	pub black_id: Uuid,
+	pub start_board: String,
+	pub board: String,
+	pub moves: Vec<String>,
+	pub result: Option<String>,
+	pub termination: Option<String>,
+	pub time_control: Option<TimeControl>,
+	pub halfmove_clock: i32,
+	pub position_hashes: Vec<i64>,
+	pub rated: bool,
+}
+
+impl NewGame {
+	pub fn new(white_id: Uuid, black_id: Uuid) -> Self {
+		let board = chess::Board::default();
+
+		Self {
+			white_id,
+			black_id,
+			start_board: board.to_string(),
+			board: board.to_string(),
+			moves: vec![],
+			result: None,
+			termination: None,
+			time_control: None,
+			halfmove_clock: 0,
+			position_hashes: vec![position_hash(&board)],
+			rated: true,
+		}
+	}
+
+	pub async fn insert<'exec, E>(&self, conn: E) -> Result<Game, sqlx::Error>
+	where
+		E: sqlx::Executor<'exec, Database = sqlx::Postgres>,
+	{
+		let (time_control, time_base_ms, time_increment_ms, days_per_move) = match &self.time_control {
+			Some(time_control) => {
+				let (name, base_ms, increment_ms, days) = time_control.to_columns();
+				(Some(name), base_ms, increment_ms, days)
+			}
+			None => (None, None, None, None),
+		};
+		let clock_ms = self.time_control.map(|time_control| time_control.initial_ms());
+
+		sqlx::query_as!(
+			Game,
+			"insert into games (white_id, black_id, start_board, board, moves, result, termination, time_control, time_base_ms, time_increment_ms, days_per_move, white_clock_ms, black_clock_ms, halfmove_clock, position_hashes, rated)
+values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $12, $13, $14, $15) returning *",
+			self.white_id,
+			self.black_id,
+			self.start_board,
+			self.board,
+			self.moves,
+			self.result,
+			self.termination,
+			time_control,
+			time_base_ms,
+			time_increment_ms,
+			days_per_move,
+			clock_ms,
+			self.halfmove_clock,
+			self.position_hashes,
+			self.rated
+		)
+		.fetch_one(conn)
+		.await
+	}
 }
@@ -0,0 +1,18 @@
+use chrono::NaiveDateTime;
+use sqlx::{types::Uuid, FromRow};
+
+#[derive(Debug, Clone, FromRow)]
+pub struct Seek {
+	pub id: Uuid,
+	pub user_id: Uuid,
+	pub color: Option<String>,
+	pub time_control: Option<String>,
+	pub time_base_ms: Option<i64>,
+	pub time_increment_ms: Option<i64>,
+	pub days_per_move: Option<i32>,
+	pub rating_min: Option<i32>,
+	pub rating_max: Option<i32>,
+	pub game_id: Option<Uuid>,
+	pub created_at: NaiveDateTime,
+	pub rated: bool,
+}
@@ -0,0 +1,153 @@
+use super::{
+	clock::can_checkmate,
+	game::{color_from_column, color_to_column, Game, Termination},
+};
+use chess::{Board, ChessMove, Color, Piece};
+use serde::{Deserialize, Serialize};
+
+/// Plies without a capture or pawn move after which either player may claim a draw.
+pub const FIFTY_MOVE_PLIES: i32 = 100;
+/// Plies without a capture or pawn move after which the game is drawn automatically.
+pub const SEVENTY_FIVE_MOVE_PLIES: i32 = 150;
+
+/// A player may only offer a draw once in this many of their own moves.
+pub const DRAW_OFFER_INTERVAL_MOVES: i32 = 5;
+
+/// The halfmove clock after `mv` is played on `board`: captures and pawn moves reset it.
+pub fn next_halfmove_clock(board: &Board, mv: ChessMove, halfmove_clock: i32) -> i32 {
+	let is_pawn_move = board.piece_on(mv.get_source()) == Some(Piece::Pawn);
+	let is_capture = board.piece_on(mv.get_dest()).is_some();
+
+	if is_pawn_move || is_capture {
+		0
+	} else {
+		halfmove_clock + 1
+	}
+}
+
+/// The halfmove clock recorded in `fen`, which `chess::Board` drops when parsing it.
+pub fn fen_halfmove_clock(fen: &str) -> i32 {
+	fen.split_whitespace().nth(4).and_then(|n| n.parse().ok()).unwrap_or(0)
+}
+
+/// `board` as a FEN carrying `halfmove_clock`, since `chess::Board` always writes a clock of zero.
+pub fn fen_with_halfmove_clock(board: &Board, halfmove_clock: i32) -> String {
+	let fen = board.to_string();
+	let position = fen.rsplitn(3, ' ').nth(2).unwrap_or(&fen);
+	format!("{} {} 1", position, halfmove_clock)
+}
+
+/// The Zobrist hash of `board`, as stored in the `position_hashes` column.
+pub fn position_hash(board: &Board) -> i64 {
+	board.get_hash() as i64
+}
+
+/// How many times the current position has occurred, including now.
+pub fn repetitions(game: &Game) -> usize {
+	match game.position_hashes.last() {
+		Some(current) => game.position_hashes.iter().filter(|hash| *hash == current).count(),
+		None => 1,
+	}
+}
+
+/// Whether neither side can checkmate by any sequence of legal moves.
+pub fn is_dead_position(board: &Board) -> bool {
+	!can_checkmate(board, Color::White) && !can_checkmate(board, Color::Black)
+}
+
+/// The draw a player may claim in the current position, if any.
+pub fn claimable(game: &Game) -> Option<Termination> {
+	if repetitions(game) >= 3 {
+		Some(Termination::ThreefoldRepetition)
+	} else if game.halfmove_clock >= FIFTY_MOVE_PLIES {
+		Some(Termination::FiftyMoveRule)
+	} else {
+		None
+	}
+}
+
+/// The draw the current position ends the game with, whether or not anyone claims it.
+pub fn automatic(game: &Game) -> Option<Termination> {
+	if repetitions(game) >= 5 {
+		Some(Termination::FivefoldRepetition)
+	} else if game.halfmove_clock >= SEVENTY_FIVE_MOVE_PLIES {
+		Some(Termination::SeventyFiveMoveRule)
+	} else if is_dead_position(&game.board.current_position()) {
+		Some(Termination::InsufficientMaterial)
+	} else {
+		None
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DrawOfferError {
+	#[error("A draw offer is already pending")]
+	AlreadyPending,
+	#[error("A draw can only be offered once every {} moves", DRAW_OFFER_INTERVAL_MOVES)]
+	TooSoon,
+	#[error("There is no draw offer from your opponent to respond to")]
+	NoOffer,
+}
+
+/// The draw offer awaiting a response, if any, and the ply at which each side last offered a draw.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct DrawOffers {
+	pub pending: Option<Color>,
+	pub white_ply: Option<i32>,
+	pub black_ply: Option<i32>,
+}
+
+impl DrawOffers {
+	pub fn from_columns(pending: Option<&str>, white_ply: Option<i32>, black_ply: Option<i32>) -> Self {
+		Self {
+			pending: pending.and_then(color_from_column),
+			white_ply,
+			black_ply,
+		}
+	}
+
+	/// The value of the `draw_offered_by` column.
+	pub fn pending_column(&self) -> Option<&'static str> {
+		self.pending.map(color_to_column)
+	}
+
+	fn last_ply_of(&mut self, color: Color) -> &mut Option<i32> {
+		match color {
+			Color::White => &mut self.white_ply,
+			Color::Black => &mut self.black_ply,
+		}
+	}
+
+	pub fn offer(&mut self, by: Color, ply: i32) -> Result<(), DrawOfferError> {
+		if self.pending.is_some() {
+			return Err(DrawOfferError::AlreadyPending);
+		}
+
+		let last_ply = self.last_ply_of(by);
+		if matches!(*last_ply, Some(last) if ply - last < DRAW_OFFER_INTERVAL_MOVES * 2) {
+			return Err(DrawOfferError::TooSoon);
+		}
+
+		*last_ply = Some(ply);
+		self.pending = Some(by);
+		Ok(())
+	}
+
+	/// Accepts or declines the pending offer; `by` is `None` when the player controls both sides.
+	pub fn respond(&mut self, by: Option<Color>) -> Result<(), DrawOfferError> {
+		match self.pending {
+			Some(offered_by) if by != Some(offered_by) => {
+				self.pending = None;
+				Ok(())
+			}
+			_ => Err(DrawOfferError::NoOffer),
+		}
+	}
+
+	/// Withdraws an offer that `mover` made a move instead of responding to.
+	pub fn expire(&mut self, mover: Color) {
+		if self.pending == Some(!mover) {
+			self.pending = None;
+		}
+	}
+}
@@ -1,17 +1,21 @@
 use super::{
+	clock::{Clock, TimeControl},
 	db,
-	r#move::EndOfGameState,
+	draw::{self, DrawOffers},
+	r#move::{EndOfGameState, PieceStyle, ResolveError, SANChessMove, SANError},
 	user::{User, UserWithAccounts},
 };
+use crate::pgn;
 use async_std::prelude::*;
-use itertools::Itertools;
 use chess::{Color, GameResult};
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sqlx::types::Uuid;
 use std::{
 	convert::TryFrom,
 	fmt::{self, Display, Formatter},
 };
+use strum::{EnumString, IntoStaticStr};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Game {
@@ -25,10 +29,89 @@ pub struct Game {
 	pub side_to_move: Color,
 	pub moves: Vec<String>,
 	pub result: Option<GameResult>,
+	pub termination: Option<Termination>,
 	pub pgn: Pgn,
+	pub start_board: String,
+	pub clock: Option<Clock>,
+	/// Plies since the last capture or pawn move.
+	pub halfmove_clock: i32,
+	/// Zobrist hashes of every position reached so far, starting with `start_board`.
+	#[serde(skip)]
+	pub position_hashes: Vec<i64>,
+	pub draw_offers: DrawOffers,
+	pub takeback_requested_by: Option<Color>,
+	/// Whether the result counts towards the players' ratings.
+	pub rated: bool,
+	pub created_at: NaiveDateTime,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The value stored in columns that record a side, such as `draw_offered_by`.
+pub fn color_to_column(color: Color) -> &'static str {
+	match color {
+		Color::White => "White",
+		Color::Black => "Black",
+	}
+}
+
+pub fn color_from_column(column: &str) -> Option<Color> {
+	match column {
+		"White" => Some(Color::White),
+		"Black" => Some(Color::Black),
+		_ => None,
+	}
+}
+
+/// Why a game ended; more specific than its [`GameResult`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, IntoStaticStr, EnumString)]
+pub enum Termination {
+	Checkmate,
+	Resignation,
+	Stalemate,
+	DrawAgreed,
+	/// A draw declared without a recorded reason.
+	DrawClaimed,
+	ThreefoldRepetition,
+	FivefoldRepetition,
+	FiftyMoveRule,
+	SeventyFiveMoveRule,
+	InsufficientMaterial,
+	Timeout,
+	Abandoned,
+}
+
+impl Termination {
+	/// The value of the PGN `Termination` tag, which only distinguishes games that didn't end normally.
+	pub fn pgn_tag(&self) -> &'static str {
+		match self {
+			Termination::Timeout => "time forfeit",
+			Termination::Abandoned => "abandoned",
+			_ => "normal",
+		}
+	}
+
+	/// The termination a PGN `Termination` tag names, if it's one the result doesn't already imply.
+	pub fn from_pgn_tag(tag: &str) -> Option<Self> {
+		match tag {
+			"time forfeit" => Some(Termination::Timeout),
+			"abandoned" => Some(Termination::Abandoned),
+			_ => None,
+		}
+	}
+}
+
+impl From<GameResult> for Termination {
+	fn from(result: GameResult) -> Self {
+		match result {
+			GameResult::WhiteCheckmates | GameResult::BlackCheckmates => Termination::Checkmate,
+			GameResult::WhiteResigns | GameResult::BlackResigns => Termination::Resignation,
+			GameResult::Stalemate => Termination::Stalemate,
+			GameResult::DrawAccepted => Termination::DrawAgreed,
+			GameResult::DrawDeclared => Termination::DrawClaimed,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Pgn(String);
 
 impl Display for Pgn {
@@ -37,43 +120,118 @@ impl Display for Pgn {
 	}
 }
 
-impl<'game, 'moves> From<(&'game chess::Game, &'moves [String])> for Pgn {
-	fn from(value: (&'game chess::Game, &'moves [String])) -> Self {
-		let mut pgn = value
-			.1
-			.chunks(2)
-			.enumerate()
-			.map(|(i, moves)| {
-				format!(
-					"{}. {}",
-					i + 1,
-					moves.join(" ")
-				)
-			})
-			.intersperse(" ".to_owned())
-			.collect::<String>();
-		pgn += &format!(" {}", EndOfGameState::from(value.0.result()));
-		Pgn(pgn)
+impl From<&pgn::Pgn> for Pgn {
+	fn from(pgn: &pgn::Pgn) -> Self {
+		Pgn(pgn.to_string())
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GameError {
+	#[error(transparent)]
+	Chess(#[from] chess::Error),
+	#[error(transparent)]
+	SAN(#[from] SANError),
+	#[error(transparent)]
+	Resolve(#[from] ResolveError),
+	#[error("game {id} is corrupt: {reason}")]
+	Corrupt { id: Uuid, reason: String },
+}
+
+/// The board and position hashes reached by playing `moves` from `start_board`, along with the resulting halfmove clock,
+/// which carries on from the one in the `start_board` FEN.
+fn play_moves(start_board: &str, moves: &[String]) -> Result<(chess::Game, i32, Vec<i64>), GameError> {
+	let mut halfmove_clock = draw::fen_halfmove_clock(start_board);
+	let start_board: chess::Board = start_board.parse()?;
+	let mut board = chess::Game::new_with_board(start_board);
+	let mut position_hashes = vec![draw::position_hash(&start_board)];
+
+	for mv in moves {
+		let position = board.current_position();
+		let board_move = mv.parse::<SANChessMove>()?.resolve(&position)?;
+
+		halfmove_clock = draw::next_halfmove_clock(&position, board_move, halfmove_clock);
+		board.make_move(board_move);
+		position_hashes.push(draw::position_hash(&board.current_position()));
 	}
+
+	Ok((board, halfmove_clock, position_hashes))
 }
 
 impl TryFrom<db::Game> for Game {
-	type Error = chess::Error;
+	type Error = GameError;
 
+	/// The board is rebuilt from the start position and move list rather than trusted from the `board` column,
+	/// which must agree with it.
 	fn try_from(game: db::Game) -> Result<Self, Self::Error> {
-		let board: chess::Game = game.board.parse()?;
-		let pgn: Pgn = (&board, game.moves.as_slice()).into();
+		let corrupt = |reason: String| GameError::Corrupt { id: game.id, reason };
+
+		let (board, halfmove_clock, position_hashes) = play_moves(&game.start_board, &game.moves)
+			.map_err(|e| corrupt(format!("the moves can't be replayed: {}", e)))?;
+		let replayed = board.current_position().to_string();
+		let stored = game.board.parse::<chess::Board>().ok().map(|board| board.to_string());
+		if stored.as_deref() != Some(replayed.as_str()) {
+			return Err(corrupt(format!(
+				"the moves lead to \"{}\" but \"{}\" is stored",
+				replayed, game.board
+			)));
+		}
+		// games from before draw tracking have neither column filled in until their next move
+		if !game.position_hashes.is_empty() {
+			if game.position_hashes != position_hashes {
+				return Err(corrupt(format!(
+					"the moves reach {} positions that don't match the {} stored position hashes",
+					position_hashes.len(),
+					game.position_hashes.len()
+				)));
+			}
+			if game.halfmove_clock != halfmove_clock {
+				return Err(corrupt(format!(
+					"the moves leave a halfmove clock of {} but {} is stored",
+					halfmove_clock, game.halfmove_clock
+				)));
+			}
+		}
+
+		let clock = TimeControl::from_columns(
+			game.time_control.as_deref(),
+			game.time_base_ms,
+			game.time_increment_ms,
+			game.days_per_move,
+		)
+		.map(|time_control| Clock {
+			time_control,
+			white_ms: game.white_clock_ms.unwrap_or_else(|| time_control.initial_ms()),
+			black_ms: game.black_clock_ms.unwrap_or_else(|| time_control.initial_ms()),
+			turn_started_at: game.turn_started_at,
+		});
 
-		Ok(Self {
+		let mut game = Self {
 			id: game.id,
 			white_id: game.white_id,
 			black_id: game.black_id,
 			side_to_move: board.side_to_move(),
 			board,
 			moves: game.moves,
-			pgn,
+			pgn: Pgn::default(),
 			result: game.result.and_then(|res| res.parse().ok()),
-		})
+			termination: game.termination.and_then(|termination| termination.parse().ok()),
+			start_board: game.start_board,
+			clock,
+			halfmove_clock,
+			position_hashes,
+			draw_offers: DrawOffers::from_columns(
+				game.draw_offered_by.as_deref(),
+				game.white_draw_offer_ply,
+				game.black_draw_offer_ply,
+			),
+			takeback_requested_by: game.takeback_requested_by.as_deref().and_then(color_from_column),
+			rated: game.rated,
+			created_at: game.created_at,
+		};
+		game.pgn = (&game.to_pgn()?).into();
+
+		Ok(game)
 	}
 }
 
@@ -90,11 +248,71 @@ impl Game {
 		}
 	}
 
-	pub fn reload(&mut self) -> &Self {
+	pub fn reload(&mut self) -> Result<&Self, SANError> {
 		self.side_to_move = self.board.side_to_move();
 		self.result = self.board.result();
-		self.pgn = (&self.board, self.moves.as_slice()).into();
-		self
+		self.termination = self.result.map(Termination::from);
+		self.pgn = (&self.to_pgn()?).into();
+		Ok(self)
+	}
+
+	/// Rebuilds the board, halfmove clock and position history by replaying the move list from the start position.
+	pub fn replay(&mut self) -> Result<&Self, GameError> {
+		let (board, halfmove_clock, position_hashes) = play_moves(&self.start_board, &self.moves)?;
+
+		self.board = board;
+		self.halfmove_clock = halfmove_clock;
+		self.position_hashes = position_hashes;
+		self.reload()?;
+		Ok(self)
+	}
+
+	/// Winds a game in progress back by `plies` and hides its pending offers, as spectators see it,
+	/// so that they can't relay moves to the players.
+	pub fn delay(&mut self, plies: usize) -> Result<&Self, GameError> {
+		if self.result.is_none() && plies > 0 {
+			let shown = self.moves.len().saturating_sub(plies);
+			self.moves.truncate(shown);
+			self.replay()?;
+			self.draw_offers = DrawOffers::default();
+			self.takeback_requested_by = None;
+		}
+
+		Ok(self)
+	}
+
+	/// Rewrites the move list and PGN with the given piece style; the game can't be reloaded afterwards.
+	pub fn localize(&mut self, style: PieceStyle) -> Result<&Self, SANError> {
+		self.pgn = Pgn(self.to_pgn()?.styled(style).to_string());
+		self.moves = self
+			.moves
+			.iter()
+			.map(|mv| Ok(mv.parse::<SANChessMove>()?.styled(style).to_string()))
+			.collect::<Result<_, SANError>>()?;
+
+		Ok(self)
+	}
+
+	pub fn to_pgn(&self) -> Result<pgn::Pgn, SANError> {
+		let mut pgn = pgn::Pgn::new(EndOfGameState::from(self.result));
+		pgn.set_tag("Date", self.created_at.format("%Y.%m.%d").to_string())
+			.set_tag("White", self.white_id.to_string())
+			.set_tag("Black", self.black_id.to_string())
+			.set_tag("GameId", self.id.to_string())
+			.set_tag(
+				"Termination",
+				self.termination.map_or("unterminated", |termination| termination.pgn_tag()),
+			);
+
+		if self.start_board != chess::Board::default().to_string() {
+			pgn.set_tag("SetUp", "1").set_tag("FEN", self.start_board.clone());
+		}
+
+		for mv in &self.moves {
+			pgn.moves.plies.push(pgn::Ply::new(mv.parse()?));
+		}
+
+		Ok(pgn)
 	}
 
 	pub async fn with_users<'exec, E>(self, conn: E) -> Result<GameWithUsers, sqlx::Error>
@@ -113,7 +331,15 @@ impl Game {
 			side_to_move: self.side_to_move,
 			moves: self.moves,
 			result: self.result,
+			termination: self.termination,
 			pgn: self.pgn,
+			start_board: self.start_board,
+			clock: self.clock,
+			halfmove_clock: self.halfmove_clock,
+			draw_offers: self.draw_offers,
+			takeback_requested_by: self.takeback_requested_by,
+			rated: self.rated,
+			created_at: self.created_at,
 		})
 	}
 }
@@ -155,5 +381,13 @@ pub struct GameWithUsers {
 	pub side_to_move: Color,
 	pub moves: Vec<String>,
 	pub result: Option<GameResult>,
+	pub termination: Option<Termination>,
 	pub pgn: Pgn,
+	pub start_board: String,
+	pub clock: Option<Clock>,
+	pub halfmove_clock: i32,
+	pub draw_offers: DrawOffers,
+	pub takeback_requested_by: Option<Color>,
+	pub rated: bool,
+	pub created_at: NaiveDateTime,
 }
@@ -0,0 +1,21 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Uuid, FromRow};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct GameMove {
+	#[serde(with = "crate::serde::uuid")]
+	pub id: Uuid,
+	#[serde(with = "crate::serde::uuid")]
+	pub game_id: Uuid,
+	pub ply: i32,
+	pub san: String,
+	pub uci: String,
+	pub fen: String,
+	#[serde(with = "crate::serde::uuid")]
+	pub user_id: Uuid,
+	pub time_spent_ms: i64,
+	pub created_at: NaiveDateTime,
+	/// When the move was undone by an accepted takeback.
+	pub taken_back_at: Option<NaiveDateTime>,
+}
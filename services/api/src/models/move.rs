@@ -1,5 +1,5 @@
-use chess::{File, GameResult, Piece, Rank, Square};
-use nom::{Err, IResult, branch::alt, bytes::complete::{tag, take}, combinator::{complete, map, map_res, opt, value}, error::{convert_error, VerboseError}, sequence::tuple};
+use chess::{Board, BoardStatus, ChessMove, Color, File, GameResult, MoveGen, Piece, Rank, Square};
+use nom::{Err, IResult, branch::alt, bytes::complete::{tag, take}, combinator::{all_consuming, map, map_res, opt, value, verify}, error::{convert_error, VerboseError}, sequence::{preceded, tuple}};
 use serde::{Deserialize, Serialize};
 use std::{
 	convert::{TryFrom, TryInto},
@@ -7,7 +7,7 @@ use std::{
 	str::FromStr,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CheckState {
 	None,
 	Check,
@@ -15,22 +15,33 @@ pub enum CheckState {
 	Mate,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CastleSide {
 	King,
 	Queen,
 }
 
+impl Display for CheckState {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			CheckState::None => "",
+			CheckState::Check => "+",
+			CheckState::Double => "++",
+			CheckState::Mate => "#",
+		})
+	}
+}
+
 impl Display for CastleSide {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		f.write_str(match self {
-			CastleSide::King => "0-0",
-			CastleSide::Queen => "0-0-0",
+			CastleSide::King => "O-O",
+			CastleSide::Queen => "O-O-O",
 		})
 	}
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EndOfGameState {
 	None,
 	WhiteWins,
@@ -43,7 +54,7 @@ impl Display for EndOfGameState {
 		f.write_str(match self {
 			EndOfGameState::None => "*",
 			EndOfGameState::WhiteWins => "1-0",
-			EndOfGameState::Draw => "½-½",
+			EndOfGameState::Draw => "1/2-1/2",
 			EndOfGameState::BlackWins => "0-1",
 		})
 	}
@@ -68,7 +79,7 @@ impl From<Option<GameResult>> for EndOfGameState {
 	}
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(try_from = "&str", into = "String")]
 pub enum SANChessMove {
 	Move {
@@ -80,7 +91,7 @@ pub enum SANChessMove {
 		promotion: Option<Piece>,
 		check: CheckState,
 	},
-	Castle(CastleSide),
+	Castle(CastleSide, CheckState),
 	OfferDraw,
 	EOG(EndOfGameState),
 }
@@ -89,6 +100,28 @@ pub enum SANChessMove {
 pub enum SANError {
 	#[error("Parse error: {0}")]
 	Parse(String),
+	#[error("there is no piece on {0}")]
+	EmptySquare(Square),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ResolveError {
+	#[error("{0} is not a move")]
+	NotAMove(String),
+	#[error("no piece can play {0}")]
+	NoMatchingPiece(String),
+	#[error("{san} is ambiguous: it could be any of {}", candidates.join(", "))]
+	Ambiguous { san: String, candidates: Vec<String> },
+	#[error("{0} would leave the king in check")]
+	LeavesKingInCheck(String),
+	#[error("{0} must promote to a knight, bishop, rook or queen")]
+	MissingPromotion(String),
+	#[error("{0} can't promote to that piece")]
+	WrongPromotion(String),
+	#[error("{0} captures a piece, so it must be written with an x")]
+	MissingCapture(String),
+	#[error("{0} doesn't capture anything")]
+	NothingToCapture(String),
 }
 
 fn parse_piece(input: &str) -> IResult<&str, Piece, VerboseError<&str>> {
@@ -112,8 +145,8 @@ fn parse_square(input: &str) -> IResult<&str, Square, VerboseError<&str>> {
 fn parse_check_state(input: &str) -> IResult<&str, CheckState, VerboseError<&str>> {
 	map(
 		opt(alt((
-			value(CheckState::Check, tag("+")),
 			value(CheckState::Double, tag("++")),
+			value(CheckState::Check, tag("+")),
 			value(CheckState::Mate, tag("#")),
 		))),
 		|maybe_state| maybe_state.unwrap_or(CheckState::None),
@@ -126,8 +159,8 @@ fn parse_draw(input: &str) -> IResult<&str, SANChessMove, VerboseError<&str>> {
 
 fn parse_castle_side(input: &str) -> IResult<&str, CastleSide, VerboseError<&str>> {
 	alt((
-		value(CastleSide::King, alt((tag("0-0"), tag("O-O")))),
 		value(CastleSide::Queen, alt((tag("0-0-0"), tag("O-O-O")))),
+		value(CastleSide::King, alt((tag("0-0"), tag("O-O")))),
 	))(input)
 }
 
@@ -142,7 +175,7 @@ fn parse_eog_state(input: &str) -> IResult<&str, EndOfGameState, VerboseError<&s
 
 fn parse_rank(input: &str) -> IResult<&str, Rank, VerboseError<&str>> {
 	map(
-		map_res(take(1usize), |c: &str| c.parse::<usize>()),
+		verify(map_res(take(1usize), |c: &str| c.parse::<usize>()), |index: &usize| (1..=8).contains(index)),
 		|index| Rank::from_index(index - 1),
 	)(input)
 }
@@ -162,25 +195,25 @@ fn parse_file(input: &str) -> IResult<&str, File, VerboseError<&str>> {
 
 fn parse_disambiguator(input: &str) -> IResult<&str, (Option<File>, Option<Rank>), VerboseError<&str>> {
 	alt((
+		map(tuple((parse_file, parse_rank)), |(file, rank)| (Some(file), Some(rank))),
 		map(parse_file, |file| (Some(file), None)),
 		map(parse_rank, |rank| (None, Some(rank))),
-		map(tuple((parse_file, parse_rank)), |(file, rank)| (Some(file), Some(rank)))
 	))(input)
 }
 
 fn parse_move<'a>(input: &'a str) -> IResult<&'a str, SANChessMove, VerboseError<&'a str>> {
-	complete(alt((
-		map(parse_castle_side, |side| SANChessMove::Castle(side)),
+	all_consuming(alt((
+		map(tuple((parse_castle_side, parse_check_state)), |(side, check)| SANChessMove::Castle(side, check)),
 		parse_draw,
 		map(parse_eog_state, |state| SANChessMove::EOG(state)),
 		map(
 			tuple((
 				map(opt(parse_piece), |maybe_piece| maybe_piece.unwrap_or(Piece::Pawn)),
 				alt((
-					map(parse_square, |square| ((None, None), false, square)),
-					tuple((parse_disambiguator, parse_capture, parse_square))
+					tuple((parse_disambiguator, parse_capture, parse_square)),
+					map(tuple((parse_capture, parse_square)), |(capture, square)| ((None, None), capture, square)),
 				)),
-				opt(parse_piece),
+				parse_promotion,
 				parse_check_state,
 			)),
 			|(piece, ((src_file, src_rank), capture, dest), promotion, check)| SANChessMove::Move {
@@ -196,6 +229,253 @@ fn parse_move<'a>(input: &'a str) -> IResult<&'a str, SANChessMove, VerboseError
 	)))(input)
 }
 
+fn parse_promotion(input: &str) -> IResult<&str, Option<Piece>, VerboseError<&str>> {
+	opt(preceded(opt(tag("=")), parse_piece))(input)
+}
+
+fn parse_long_algebraic(input: &str) -> IResult<&str, SANChessMove, VerboseError<&str>> {
+	all_consuming(alt((
+		map(tuple((parse_castle_side, parse_check_state)), |(side, check)| SANChessMove::Castle(side, check)),
+		map(
+			tuple((
+				map(opt(parse_piece), |maybe_piece| maybe_piece.unwrap_or(Piece::Pawn)),
+				parse_square,
+				alt((value(false, tag("-")), value(true, tag("x")))),
+				parse_square,
+				parse_promotion,
+				parse_check_state,
+			)),
+			|(piece, src, capture, dest, promotion, check)| SANChessMove::Move {
+				piece,
+				src_file: Some(src.get_file()),
+				src_rank: Some(src.get_rank()),
+				capture,
+				dest,
+				promotion,
+				check,
+			},
+		),
+	)))(input)
+}
+
+fn parse_uci(input: &str) -> IResult<&str, (Square, Square, Option<Piece>), VerboseError<&str>> {
+	all_consuming(tuple((
+		parse_square,
+		parse_square,
+		opt(alt((
+			value(Piece::Knight, tag("n")),
+			value(Piece::Bishop, tag("b")),
+			value(Piece::Rook, tag("r")),
+			value(Piece::Queen, tag("q")),
+		))),
+	)))(input)
+}
+
+fn to_san_error(input: &str, e: Err<VerboseError<&str>>) -> SANError {
+	SANError::Parse(match e {
+		Err::Error(e) | Err::Failure(e) => convert_error(input, e),
+		Err::Incomplete(_) => unreachable!(), // all move parsers consume the entire string, so an imcomplete parser is impossible
+	})
+}
+
+/// The notations a move may be submitted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Notation {
+	/// Standard algebraic notation, e.g. `Nf3`.
+	SAN,
+	/// Long algebraic notation, e.g. `Ng1-f3` or `e4xd5`.
+	LAN,
+	/// The notation of the Universal Chess Interface, e.g. `g1f3` or `e7e8q`.
+	UCI,
+}
+
+impl Notation {
+	/// Guesses the notation of a move; anything that isn't recognizably UCI or long algebraic is assumed to be SAN.
+	pub fn detect(text: &str) -> Self {
+		if parse_uci(text).is_ok() {
+			Notation::UCI
+		} else if parse_long_algebraic(text).is_ok() {
+			Notation::LAN
+		} else {
+			Notation::SAN
+		}
+	}
+
+	/// Reads a move in this notation as SAN, using the position to recover what UCI leaves out.
+	pub fn parse(self, text: &str, board: &Board) -> Result<SANChessMove, SANError> {
+		match self {
+			Notation::SAN => text.parse(),
+			Notation::LAN => parse_long_algebraic(text)
+				.map(|(_, mv)| mv)
+				.map_err(|e| to_san_error(text, e)),
+			Notation::UCI => {
+				let (_, (src, dest, promotion)) = parse_uci(text).map_err(|e| to_san_error(text, e))?;
+				let piece = board.piece_on(src).ok_or(SANError::EmptySquare(src))?;
+
+				let file_delta = dest.get_file().to_index() as i8 - src.get_file().to_index() as i8;
+				if piece == Piece::King && file_delta.abs() == 2 {
+					let side = if file_delta > 0 {
+						CastleSide::King
+					} else {
+						CastleSide::Queen
+					};
+
+					return Ok(SANChessMove::Castle(side, CheckState::None));
+				}
+
+				Ok(SANChessMove::Move {
+					piece,
+					src_file: Some(src.get_file()),
+					src_rank: Some(src.get_rank()),
+					// a pawn moving diagonally onto an empty square captures en passant
+					capture: board.piece_on(dest).is_some() || (piece == Piece::Pawn && file_delta != 0),
+					dest,
+					promotion,
+					check: CheckState::None,
+				})
+			}
+		}
+	}
+}
+
+/// Whether `piece` could move from `src` to `dest` on `board`, without considering the safety of its king.
+fn reaches(board: &Board, piece: Piece, src: Square, dest: Square) -> bool {
+	let color = match board.color_on(src) {
+		Some(color) => color,
+		None => return false,
+	};
+
+	if board.color_on(dest) == Some(color) {
+		return false;
+	}
+
+	let file_delta = dest.get_file().to_index() as i8 - src.get_file().to_index() as i8;
+	let rank_delta = dest.get_rank().to_index() as i8 - src.get_rank().to_index() as i8;
+
+	let path_is_clear = || {
+		let (file_step, rank_step) = (file_delta.signum(), rank_delta.signum());
+		let steps = file_delta.abs().max(rank_delta.abs());
+
+		(1..steps).all(|step| {
+			let square = Square::make_square(
+				Rank::from_index((src.get_rank().to_index() as i8 + rank_step * step) as usize),
+				File::from_index((src.get_file().to_index() as i8 + file_step * step) as usize),
+			);
+			board.piece_on(square).is_none()
+		})
+	};
+
+	match piece {
+		Piece::Knight => (file_delta.abs(), rank_delta.abs()) == (1, 2) || (file_delta.abs(), rank_delta.abs()) == (2, 1),
+		Piece::King => file_delta.abs().max(rank_delta.abs()) == 1,
+		Piece::Bishop => file_delta.abs() == rank_delta.abs() && path_is_clear(),
+		Piece::Rook => (file_delta == 0 || rank_delta == 0) && path_is_clear(),
+		Piece::Queen => (file_delta.abs() == rank_delta.abs() || file_delta == 0 || rank_delta == 0) && path_is_clear(),
+		Piece::Pawn => {
+			let (forward, start_rank) = match color {
+				Color::White => (1, 1),
+				Color::Black => (-1, 6),
+			};
+
+			if file_delta == 0 {
+				board.piece_on(dest).is_none()
+					&& (rank_delta == forward
+						|| (rank_delta == 2 * forward && src.get_rank().to_index() == start_rank && path_is_clear()))
+			} else {
+				// the en passant square is that of the pawn which may be captured, one rank behind the destination
+				let en_passant = board.en_passant().map_or(false, |square| {
+					square.get_file() == dest.get_file() && square.get_rank() == src.get_rank()
+				});
+
+				file_delta.abs() == 1 && rank_delta == forward && (board.piece_on(dest).is_some() || en_passant)
+			}
+		}
+	}
+}
+
+impl SANChessMove {
+	/// Finds the unique legal move this SAN describes in the given position.
+	pub fn resolve(&self, board: &Board) -> Result<ChessMove, ResolveError> {
+		let san = self.to_string();
+		let color = board.side_to_move();
+
+		let (piece, capture, src_file, src_rank, dest, promotion) = match self {
+			SANChessMove::Move {
+				piece,
+				capture,
+				src_file,
+				src_rank,
+				dest,
+				promotion,
+				..
+			} => (*piece, *capture, *src_file, *src_rank, *dest, *promotion),
+			SANChessMove::Castle(side, _) => {
+				let king = board.king_square(color);
+				let dest = Square::make_square(
+					king.get_rank(),
+					match side {
+						CastleSide::King => File::G,
+						CastleSide::Queen => File::C,
+					},
+				);
+
+				let castle = ChessMove::new(king, dest, None);
+				return if king.get_file() == File::E && board.legal(castle) {
+					Ok(castle)
+				} else {
+					Err(ResolveError::NoMatchingPiece(san))
+				};
+			}
+			SANChessMove::OfferDraw | SANChessMove::EOG(_) => return Err(ResolveError::NotAMove(san)),
+		};
+
+		let promotes = piece == Piece::Pawn && dest.get_rank() == color.to_their_backrank();
+		match promotion {
+			None if promotes => return Err(ResolveError::MissingPromotion(san)),
+			Some(Piece::Pawn) | Some(Piece::King) if promotes => return Err(ResolveError::WrongPromotion(san)),
+			Some(_) if !promotes => return Err(ResolveError::WrongPromotion(san)),
+			_ => {}
+		}
+
+		let candidates = (*board.pieces(piece) & *board.color_combined(color))
+			// pawn captures always name the file they're made from, so a pawn move without one stays on its file
+			.filter(|src| match src_file {
+				Some(file) => src.get_file() == file,
+				None => piece != Piece::Pawn || src.get_file() == dest.get_file(),
+			})
+			.filter(|src| src_rank.map_or(true, |rank| src.get_rank() == rank))
+			.filter(|src| reaches(board, piece, *src, dest))
+			.map(|src| ChessMove::new(src, dest, promotion))
+			.collect::<Vec<_>>();
+
+		if candidates.is_empty() {
+			return Err(ResolveError::NoMatchingPiece(san));
+		}
+
+		// every candidate shares the destination, and pawns the file, so they all capture or none do
+		let captures = board.piece_on(dest).is_some()
+			|| (piece == Piece::Pawn && candidates[0].get_source().get_file() != dest.get_file());
+		match (captures, capture) {
+			(true, false) => return Err(ResolveError::MissingCapture(san)),
+			(false, true) => return Err(ResolveError::NothingToCapture(san)),
+			_ => {}
+		}
+
+		let mut legal = candidates.into_iter().filter(|mv| board.legal(*mv)).collect::<Vec<_>>();
+		match legal.len() {
+			0 => Err(ResolveError::LeavesKingInCheck(san)),
+			1 => Ok(legal.remove(0)),
+			_ => Err(ResolveError::Ambiguous {
+				san,
+				candidates: legal
+					.into_iter()
+					.map(|mv| SANChessMove::from((board, mv)).to_string())
+					.collect(),
+			}),
+		}
+	}
+}
+
 impl<'a> TryFrom<&'a str> for SANChessMove {
 	type Error = Err<VerboseError<&'a str>>;
 
@@ -209,10 +489,7 @@ impl FromStr for SANChessMove {
 	type Err = SANError;
 
 	fn from_str<'a>(s: &'a str) -> Result<Self, Self::Err> {
-		s.try_into().map_err(|e| SANError::Parse(match e {
-			Err::Error(e) | Err::Failure(e) => convert_error(s, e),
-			Err::Incomplete(_) => unreachable!(), // parse_move consumes the entire string, so an imcomplete parser is impossible
-		}))
+		s.try_into().map_err(|e| to_san_error(s, e))
 	}
 }
 
@@ -222,21 +499,150 @@ impl From<SANChessMove> for String {
 	}
 }
 
-fn piece_to_san(piece: &Piece) -> &str {
-	match piece {
-		Piece::Bishop => "B",
-		Piece::Queen => "Q",
-		Piece::Pawn => "",
-		Piece::Rook => "R",
-		Piece::King => "K",
-		Piece::Knight => "N",
+impl<'board> From<(&'board Board, ChessMove)> for SANChessMove {
+	/// Builds the canonical SAN for a legal move: minimal disambiguation, `=` promotions and the correct check suffix.
+	fn from((board, mv): (&'board Board, ChessMove)) -> Self {
+		let src = mv.get_source();
+		let dest = mv.get_dest();
+		let piece = board.piece_on(src).unwrap_or(Piece::Pawn);
+
+		let after = board.make_move_new(mv);
+		let check = if after.status() == BoardStatus::Checkmate {
+			CheckState::Mate
+		} else if after.checkers().popcnt() > 0 {
+			CheckState::Check
+		} else {
+			CheckState::None
+		};
+
+		if piece == Piece::King && (src.get_file().to_index() as i8 - dest.get_file().to_index() as i8).abs() == 2 {
+			let side = if dest.get_file() == File::G {
+				CastleSide::King
+			} else {
+				CastleSide::Queen
+			};
+
+			return SANChessMove::Castle(side, check);
+		}
+
+		let capture = board.piece_on(dest).is_some() || (piece == Piece::Pawn && src.get_file() != dest.get_file());
+
+		let (src_file, src_rank) = if piece == Piece::Pawn {
+			(if capture { Some(src.get_file()) } else { None }, None)
+		} else {
+			let ambiguous = MoveGen::new_legal(board)
+				.filter(|other| {
+					other.get_dest() == dest
+						&& other.get_source() != src
+						&& board.piece_on(other.get_source()) == Some(piece)
+				})
+				.map(|other| other.get_source())
+				.collect::<Vec<_>>();
+
+			if ambiguous.is_empty() {
+				(None, None)
+			} else if ambiguous.iter().all(|other| other.get_file() != src.get_file()) {
+				(Some(src.get_file()), None)
+			} else if ambiguous.iter().all(|other| other.get_rank() != src.get_rank()) {
+				(None, Some(src.get_rank()))
+			} else {
+				(Some(src.get_file()), Some(src.get_rank()))
+			}
+		};
+
+		SANChessMove::Move {
+			piece,
+			capture,
+			src_rank,
+			src_file,
+			dest,
+			promotion: mv.get_promotion(),
+			check,
+		}
+	}
+}
+
+/// How pieces are written in move text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PieceStyle {
+	English,
+	Figurine,
+	German,
+	French,
+	Spanish,
+	Italian,
+	Dutch,
+}
+
+impl Default for PieceStyle {
+	fn default() -> Self {
+		PieceStyle::English
+	}
+}
+
+const WHITE_CHESS_KING: &str = "\u{2654}";
+const WHITE_CHESS_QUEEN: &str = "\u{2655}";
+const WHITE_CHESS_ROOK: &str = "\u{2656}";
+const WHITE_CHESS_BISHOP: &str = "\u{2657}";
+const WHITE_CHESS_KNIGHT: &str = "\u{2658}";
+
+fn piece_to_san(piece: &Piece, style: PieceStyle) -> &'static str {
+	match (style, piece) {
+		(_, Piece::Pawn) => "",
+		(PieceStyle::English, Piece::King) => "K",
+		(PieceStyle::English, Piece::Queen) => "Q",
+		(PieceStyle::English, Piece::Rook) => "R",
+		(PieceStyle::English, Piece::Bishop) => "B",
+		(PieceStyle::English, Piece::Knight) => "N",
+		(PieceStyle::Figurine, Piece::King) => WHITE_CHESS_KING,
+		(PieceStyle::Figurine, Piece::Queen) => WHITE_CHESS_QUEEN,
+		(PieceStyle::Figurine, Piece::Rook) => WHITE_CHESS_ROOK,
+		(PieceStyle::Figurine, Piece::Bishop) => WHITE_CHESS_BISHOP,
+		(PieceStyle::Figurine, Piece::Knight) => WHITE_CHESS_KNIGHT,
+		(PieceStyle::German, Piece::King) => "K",
+		(PieceStyle::German, Piece::Queen) => "D",
+		(PieceStyle::German, Piece::Rook) => "T",
+		(PieceStyle::German, Piece::Bishop) => "L",
+		(PieceStyle::German, Piece::Knight) => "S",
+		(PieceStyle::French, Piece::King) => "R",
+		(PieceStyle::French, Piece::Queen) => "D",
+		(PieceStyle::French, Piece::Rook) => "T",
+		(PieceStyle::French, Piece::Bishop) => "F",
+		(PieceStyle::French, Piece::Knight) => "C",
+		(PieceStyle::Spanish, Piece::King) | (PieceStyle::Italian, Piece::King) => "R",
+		(PieceStyle::Spanish, Piece::Queen) | (PieceStyle::Italian, Piece::Queen) => "D",
+		(PieceStyle::Spanish, Piece::Rook) | (PieceStyle::Italian, Piece::Rook) => "T",
+		(PieceStyle::Spanish, Piece::Bishop) | (PieceStyle::Italian, Piece::Bishop) => "A",
+		(PieceStyle::Spanish, Piece::Knight) | (PieceStyle::Italian, Piece::Knight) => "C",
+		(PieceStyle::Dutch, Piece::King) => "K",
+		(PieceStyle::Dutch, Piece::Queen) => "D",
+		(PieceStyle::Dutch, Piece::Rook) => "T",
+		(PieceStyle::Dutch, Piece::Bishop) => "L",
+		(PieceStyle::Dutch, Piece::Knight) => "P",
+	}
+}
+
+/// A move displayed with a particular piece style.
+pub struct StyledSANChessMove<'a>(&'a SANChessMove, PieceStyle);
+
+impl SANChessMove {
+	pub fn styled(&self, style: PieceStyle) -> StyledSANChessMove<'_> {
+		StyledSANChessMove(self, style)
 	}
 }
 
 impl Display for SANChessMove {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		match self {
-			SANChessMove::Castle(side) => write!(f, "{}", side),
+		write!(f, "{}", self.styled(PieceStyle::English))
+	}
+}
+
+impl<'a> Display for StyledSANChessMove<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let StyledSANChessMove(mv, style) = self;
+
+		match mv {
+			SANChessMove::Castle(side, check) => write!(f, "{}{}", side, check),
 			SANChessMove::EOG(state) => write!(f, "{}", state),
 			SANChessMove::OfferDraw => f.write_str("="),
 			SANChessMove::Move {
@@ -248,11 +654,7 @@ impl Display for SANChessMove {
 				promotion,
 				check,
 			} => {
-				f.write_str(piece_to_san(piece))?;
-
-				if let Some(src_rank) = src_rank {
-					f.write_str(&(src_rank.to_index() + 1).to_string())?;
-				}
+				f.write_str(piece_to_san(piece, *style))?;
 
 				if let Some(src_file) = src_file {
 					f.write_str(match src_file {
@@ -267,6 +669,10 @@ impl Display for SANChessMove {
 					})?;
 				}
 
+				if let Some(src_rank) = src_rank {
+					f.write_str(&(src_rank.to_index() + 1).to_string())?;
+				}
+
 				if *capture {
 					f.write_str("x")?;
 				}
@@ -274,18 +680,53 @@ impl Display for SANChessMove {
 				f.write_str(&dest.to_string())?;
 
 				if let Some(promotion) = promotion {
-					f.write_str(piece_to_san(promotion))?;
-				}
-
-				match check {
-					CheckState::Check => f.write_str("+")?,
-					CheckState::Double => f.write_str("++")?,
-					CheckState::Mate => f.write_str("#")?,
-					CheckState::None => {}
+					f.write_str("=")?;
+					f.write_str(piece_to_san(promotion, *style))?;
 				}
 
-				Ok(())
+				write!(f, "{}", check)
 			}
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::str::FromStr;
+
+	/// After 1. c4 d5.
+	const QUEENS_GAMBIT: &str = "rnbqkbnr/ppp1pppp/8/3p4/2P5/8/PP1PPPPP/RNBQKBNR w KQkq - 0 2";
+	/// After 1. Nf3 e5.
+	const KNIGHT_FACING_PAWN: &str = "rnbqkbnr/pppp1ppp/8/4p3/8/5N2/PPPPPPPP/RNBQKB1R w KQkq - 0 2";
+	/// After 1. e4 d5 2. e5 f5, so exf6 is possible en passant.
+	const EN_PASSANT: &str = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+
+	fn resolve(fen: &str, san: &str) -> Result<ChessMove, ResolveError> {
+		san.parse::<SANChessMove>().unwrap().resolve(&Board::from_str(fen).unwrap())
+	}
+
+	#[test]
+	fn pawn_moves_without_a_file_stay_on_their_file() {
+		assert!(matches!(resolve(QUEENS_GAMBIT, "d5"), Err(ResolveError::NoMatchingPiece(_))));
+		assert_eq!(resolve(QUEENS_GAMBIT, "cxd5").unwrap(), ChessMove::new(Square::C4, Square::D5, None));
+		assert_eq!(resolve(QUEENS_GAMBIT, "c5").unwrap(), ChessMove::new(Square::C4, Square::C5, None));
+	}
+
+	#[test]
+	fn captures_must_be_marked() {
+		assert!(matches!(resolve(KNIGHT_FACING_PAWN, "Ne5"), Err(ResolveError::MissingCapture(_))));
+		assert_eq!(resolve(KNIGHT_FACING_PAWN, "Nxe5").unwrap(), ChessMove::new(Square::F3, Square::E5, None));
+		assert!(matches!(resolve(KNIGHT_FACING_PAWN, "Nxd4"), Err(ResolveError::NothingToCapture(_))));
+	}
+
+	#[test]
+	fn en_passant_is_a_capture() {
+		assert_eq!(resolve(EN_PASSANT, "exf6").unwrap(), ChessMove::new(Square::E5, Square::F6, None));
+		assert!(matches!(resolve(EN_PASSANT, "f6"), Err(ResolveError::NoMatchingPiece(_))));
+
+		let board = Board::from_str(EN_PASSANT).unwrap();
+		let san = Notation::UCI.parse("e5f6", &board).unwrap();
+		assert_eq!(san.resolve(&board).unwrap(), ChessMove::new(Square::E5, Square::F6, None));
+	}
+}
@@ -0,0 +1,367 @@
+use super::{clock::TimeControl, game::Game};
+use chess::GameResult;
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Uuid, Postgres, Transaction};
+use std::{collections::HashMap, f64::consts::PI, str::FromStr};
+use strum::{EnumString, IntoStaticStr};
+
+pub const DEFAULT_RATING: f64 = 1500.0;
+const DEFAULT_DEVIATION: f64 = 350.0;
+const DEFAULT_VOLATILITY: f64 = 0.06;
+/// Constrains how much volatility can change between games.
+const TAU: f64 = 0.5;
+/// Converts between the Glicko and Glicko-2 scales.
+const SCALE: f64 = 173.7178;
+const CONVERGENCE_TOLERANCE: f64 = 0.000_001;
+
+/// Games are rated separately by how long they're expected to take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, IntoStaticStr, EnumString)]
+pub enum RatingCategory {
+	Bullet,
+	Blitz,
+	Rapid,
+	Classical,
+	Correspondence,
+}
+
+impl From<Option<&TimeControl>> for RatingCategory {
+	/// Categorizes over-the-board time controls by the expected length of a 40 move game; untimed games count as correspondence.
+	fn from(time_control: Option<&TimeControl>) -> Self {
+		let (base_ms, increment_ms) = match time_control {
+			Some(TimeControl::Fischer { base_ms, increment_ms }) => (*base_ms, *increment_ms),
+			Some(TimeControl::Bronstein { base_ms, delay_ms }) => (*base_ms, *delay_ms),
+			Some(TimeControl::Correspondence { .. }) | None => return RatingCategory::Correspondence,
+		};
+
+		match (base_ms + 40 * increment_ms) / 1000 {
+			secs if secs < 3 * 60 => RatingCategory::Bullet,
+			secs if secs < 8 * 60 => RatingCategory::Blitz,
+			secs if secs < 25 * 60 => RatingCategory::Rapid,
+			_ => RatingCategory::Classical,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Glicko {
+	pub rating: f64,
+	pub deviation: f64,
+	pub volatility: f64,
+}
+
+impl Default for Glicko {
+	fn default() -> Self {
+		Self {
+			rating: DEFAULT_RATING,
+			deviation: DEFAULT_DEVIATION,
+			volatility: DEFAULT_VOLATILITY,
+		}
+	}
+}
+
+impl Glicko {
+	/// The Glicko-2 rating after a single game against `opponent` scoring `score` (1 for a win, 0.5 for a draw and 0 for a loss).
+	pub fn update(&self, opponent: &Glicko, score: f64) -> Glicko {
+		self.update_period(&[(*opponent, score)])
+	}
+
+	/// The Glicko-2 rating after a rating period made up of `games`, each against an opponent with the player's score
+	/// as in [`Glicko::update`].
+	pub fn update_period(&self, games: &[(Glicko, f64)]) -> Glicko {
+		let mu = (self.rating - DEFAULT_RATING) / SCALE;
+		let phi = self.deviation / SCALE;
+
+		if games.is_empty() {
+			return Glicko {
+				deviation: ((phi.powi(2) + self.volatility.powi(2)).sqrt() * SCALE).min(DEFAULT_DEVIATION),
+				..*self
+			};
+		}
+
+		let mut inverse_variance = 0.0;
+		let mut improvement = 0.0;
+		for (opponent, score) in games {
+			let opponent_mu = (opponent.rating - DEFAULT_RATING) / SCALE;
+			let opponent_phi = opponent.deviation / SCALE;
+
+			let g = 1.0 / (1.0 + 3.0 * opponent_phi.powi(2) / PI.powi(2)).sqrt();
+			let expected = 1.0 / (1.0 + (-g * (mu - opponent_mu)).exp());
+			inverse_variance += g.powi(2) * expected * (1.0 - expected);
+			improvement += g * (score - expected);
+		}
+		let variance = 1.0 / inverse_variance;
+		let delta = variance * improvement;
+
+		// find the new volatility with the Illinois algorithm
+		let a = self.volatility.powi(2).ln();
+		let f = |x: f64| {
+			let ex = x.exp();
+			ex * (delta.powi(2) - phi.powi(2) - variance - ex) / (2.0 * (phi.powi(2) + variance + ex).powi(2))
+				- (x - a) / TAU.powi(2)
+		};
+
+		let mut lower = a;
+		let mut upper = if delta.powi(2) > phi.powi(2) + variance {
+			(delta.powi(2) - phi.powi(2) - variance).ln()
+		} else {
+			let mut k = 1.0;
+			while f(a - k * TAU) < 0.0 {
+				k += 1.0;
+			}
+			a - k * TAU
+		};
+
+		let mut f_lower = f(lower);
+		let mut f_upper = f(upper);
+		while (upper - lower).abs() > CONVERGENCE_TOLERANCE {
+			let next = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+			let f_next = f(next);
+			if f_next * f_upper <= 0.0 {
+				lower = upper;
+				f_lower = f_upper;
+			} else {
+				f_lower /= 2.0;
+			}
+
+			upper = next;
+			f_upper = f_next;
+		}
+
+		let volatility = (lower / 2.0).exp();
+		let pre_period_phi = (phi.powi(2) + volatility.powi(2)).sqrt();
+		let new_phi = 1.0 / (1.0 / pre_period_phi.powi(2) + 1.0 / variance).sqrt();
+		let new_mu = mu + new_phi.powi(2) * improvement;
+
+		Glicko {
+			rating: new_mu * SCALE + DEFAULT_RATING,
+			deviation: (new_phi * SCALE).min(DEFAULT_DEVIATION),
+			volatility,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserRating {
+	pub category: RatingCategory,
+	#[serde(flatten)]
+	pub glicko: Glicko,
+	/// Rated games played in this category.
+	pub games: i32,
+}
+
+impl UserRating {
+	pub async fn fetch_all<'exec, E>(user_id: &Uuid, conn: E) -> Result<Vec<Self>, sqlx::Error>
+	where
+		E: sqlx::Executor<'exec, Database = sqlx::Postgres>,
+	{
+		sqlx::query!(
+			"select * from ratings where user_id = $1 order by category",
+			user_id
+		)
+		.fetch_all(conn)
+		.await?
+		.into_iter()
+		.map(|rating| {
+			Ok(UserRating {
+				category: RatingCategory::from_str(&rating.category)
+					.map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+				glicko: Glicko {
+					rating: rating.rating,
+					deviation: rating.deviation,
+					volatility: rating.volatility,
+				},
+				games: rating.games,
+			})
+		})
+		.collect()
+	}
+
+	/// The ratings of each of `user_ids` that has any, as [`UserRating::fetch_all`] returns them.
+	pub async fn fetch_all_for<'exec, E>(user_ids: &[Uuid], conn: E) -> Result<HashMap<Uuid, Vec<Self>>, sqlx::Error>
+	where
+		E: sqlx::Executor<'exec, Database = sqlx::Postgres>,
+	{
+		let rows = sqlx::query!(
+			"select * from ratings where user_id = any($1) order by user_id, category",
+			user_ids
+		)
+		.fetch_all(conn)
+		.await?;
+
+		let mut ratings: HashMap<Uuid, Vec<Self>> = HashMap::new();
+		for rating in rows {
+			ratings.entry(rating.user_id).or_default().push(UserRating {
+				category: RatingCategory::from_str(&rating.category).map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+				glicko: Glicko {
+					rating: rating.rating,
+					deviation: rating.deviation,
+					volatility: rating.volatility,
+				},
+				games: rating.games,
+			});
+		}
+
+		Ok(ratings)
+	}
+
+	/// The user's rating in `category`, or the default for players who haven't played a rated game in it yet.
+	pub async fn fetch<'exec, E>(user_id: &Uuid, category: RatingCategory, conn: E) -> Result<Glicko, sqlx::Error>
+	where
+		E: sqlx::Executor<'exec, Database = sqlx::Postgres>,
+	{
+		let category: &str = category.into();
+		Ok(sqlx::query_as!(
+			Glicko,
+			"select rating, deviation, volatility from ratings where user_id = $1 and category = $2",
+			user_id,
+			category
+		)
+		.fetch_optional(conn)
+		.await?
+		.unwrap_or_default())
+	}
+}
+
+/// White's score for a result.
+fn white_score(result: GameResult) -> f64 {
+	match result {
+		GameResult::WhiteCheckmates | GameResult::BlackResigns => 1.0,
+		GameResult::BlackCheckmates | GameResult::WhiteResigns => 0.0,
+		GameResult::Stalemate | GameResult::DrawAccepted | GameResult::DrawDeclared => 0.5,
+	}
+}
+
+/// Updates both players' ratings for a finished rated game and records them in their rating history.
+pub async fn rate_game(game: &Game, txn: &mut Transaction<'_, Postgres>) -> Result<(), sqlx::Error> {
+	let result = match game.result {
+		Some(result) if game.rated && game.white_id != game.black_id => result,
+		_ => return Ok(()),
+	};
+
+	let category = RatingCategory::from(game.clock.as_ref().map(|clock| &clock.time_control));
+	let category_name: &str = category.into();
+
+	// lock both rows so that concurrent games can't update from the same starting ratings
+	for user_id in &[game.white_id, game.black_id] {
+		sqlx::query!(
+			"insert into ratings (user_id, category, rating, deviation, volatility) values ($1, $2, $3, $4, $5) on conflict do nothing",
+			user_id,
+			category_name,
+			DEFAULT_RATING,
+			DEFAULT_DEVIATION,
+			DEFAULT_VOLATILITY
+		)
+		.execute(&mut *txn)
+		.await?;
+	}
+
+	sqlx::query!(
+		"select user_id from ratings where user_id in ($1, $2) and category = $3 for update",
+		game.white_id,
+		game.black_id,
+		category_name
+	)
+	.fetch_all(&mut *txn)
+	.await?;
+
+	let white = UserRating::fetch(&game.white_id, category, &mut *txn).await?;
+	let black = UserRating::fetch(&game.black_id, category, &mut *txn).await?;
+	let score = white_score(result);
+
+	for (user_id, updated) in &[
+		(game.white_id, white.update(&black, score)),
+		(game.black_id, black.update(&white, 1.0 - score)),
+	] {
+		sqlx::query!(
+			"update ratings set rating = $1, deviation = $2, volatility = $3, games = games + 1, modified_at = now() where user_id = $4 and category = $5",
+			updated.rating,
+			updated.deviation,
+			updated.volatility,
+			user_id,
+			category_name
+		)
+		.execute(&mut *txn)
+		.await?;
+
+		sqlx::query!(
+			"insert into rating_history (user_id, category, game_id, rating, deviation, volatility) values ($1, $2, $3, $4, $5, $6)",
+			user_id,
+			category_name,
+			game.id,
+			updated.rating,
+			updated.deviation,
+			updated.volatility
+		)
+		.execute(&mut *txn)
+		.await?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn glicko(rating: f64, deviation: f64) -> Glicko {
+		Glicko {
+			rating,
+			deviation,
+			volatility: DEFAULT_VOLATILITY,
+		}
+	}
+
+	fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+		assert!(
+			(actual - expected).abs() <= tolerance,
+			"expected {} to be within {} of {}",
+			actual,
+			tolerance,
+			expected
+		);
+	}
+
+	/// The worked example from Glickman's "Example of the Glicko-2 system".
+	#[test]
+	fn reproduces_glickman_example() {
+		let updated = glicko(1500.0, 200.0).update_period(&[
+			(glicko(1400.0, 30.0), 1.0),
+			(glicko(1550.0, 100.0), 0.0),
+			(glicko(1700.0, 300.0), 0.0),
+		]);
+
+		assert_close(updated.rating, 1464.06, 0.05);
+		assert_close(updated.deviation, 151.52, 0.01);
+		assert_close(updated.volatility, 0.05999, 0.0001);
+	}
+
+	#[test]
+	fn draw_between_equal_players_keeps_ratings() {
+		let white = glicko(1650.0, 120.0);
+		let black = glicko(1650.0, 120.0);
+
+		assert_close(white.update(&black, 0.5).rating, 1650.0, 1e-9);
+		assert_close(black.update(&white, 0.5).rating, 1650.0, 1e-9);
+	}
+
+	#[test]
+	fn winner_gains_what_loser_loses_between_equal_players() {
+		let player = Glicko::default();
+
+		let winner = player.update(&player, 1.0);
+		let loser = player.update(&player, 0.0);
+
+		assert!(winner.rating > DEFAULT_RATING);
+		assert_close(winner.rating - DEFAULT_RATING, DEFAULT_RATING - loser.rating, 1e-9);
+		assert!(winner.deviation < DEFAULT_DEVIATION);
+	}
+
+	#[test]
+	fn deviation_grows_without_games_up_to_the_default() {
+		let rested = glicko(1500.0, 50.0).update_period(&[]);
+		assert!(rested.deviation > 50.0);
+		assert_eq!(rested.rating, 1500.0);
+
+		assert_eq!(Glicko::default().update_period(&[]).deviation, DEFAULT_DEVIATION);
+	}
+}
@@ -0,0 +1,45 @@
+use super::{clock::TimeControl, db, game::color_from_column};
+use chess::Color;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+
+/// An open invitation for anyone to play a game.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Seek {
+	#[serde(with = "crate::serde::uuid")]
+	pub id: Uuid,
+	#[serde(with = "crate::serde::uuid")]
+	pub user_id: Uuid,
+	/// The side the seeker wants to play, or `None` for either.
+	pub color: Option<Color>,
+	pub time_control: Option<TimeControl>,
+	pub rated: bool,
+	pub rating_min: Option<i32>,
+	pub rating_max: Option<i32>,
+	/// The game the seek was paired into, once it's been accepted.
+	#[serde(with = "crate::serde::option_uuid")]
+	pub game_id: Option<Uuid>,
+	pub created_at: NaiveDateTime,
+}
+
+impl From<db::Seek> for Seek {
+	fn from(seek: db::Seek) -> Self {
+		Self {
+			id: seek.id,
+			user_id: seek.user_id,
+			color: seek.color.as_deref().and_then(color_from_column),
+			time_control: TimeControl::from_columns(
+				seek.time_control.as_deref(),
+				seek.time_base_ms,
+				seek.time_increment_ms,
+				seek.days_per_move,
+			),
+			rated: seek.rated,
+			rating_min: seek.rating_min,
+			rating_max: seek.rating_max,
+			game_id: seek.game_id,
+			created_at: seek.created_at,
+		}
+	}
+}
@@ -0,0 +1,303 @@
+use super::{clock::TimeControl, db, game::Termination, rating::RatingCategory};
+use chess::GameResult;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use strum::IntoStaticStr;
+
+/// Openings are identified by this many plies from the standard starting position.
+const OPENING_PLIES: usize = 4;
+const TOP_OPENINGS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, IntoStaticStr)]
+pub enum Outcome {
+	Win,
+	Loss,
+	Draw,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Record {
+	pub wins: u32,
+	pub losses: u32,
+	pub draws: u32,
+}
+
+impl Record {
+	fn add(&mut self, outcome: Outcome) {
+		match outcome {
+			Outcome::Win => self.wins += 1,
+			Outcome::Loss => self.losses += 1,
+			Outcome::Draw => self.draws += 1,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ColorRecords {
+	pub white: Record,
+	pub black: Record,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OpponentRecord {
+	#[serde(with = "crate::serde::uuid")]
+	pub opponent_id: Uuid,
+	#[serde(flatten)]
+	pub record: Record,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Streak {
+	pub outcome: Outcome,
+	pub length: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OpeningCount {
+	pub moves: Vec<String>,
+	pub games: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UserStats {
+	pub total: Record,
+	pub by_color: ColorRecords,
+	pub by_opponent: Vec<OpponentRecord>,
+	pub by_time_control: HashMap<&'static str, Record>,
+	pub by_termination: HashMap<&'static str, Record>,
+	/// Consecutive identical outcomes ending with the most recent game.
+	pub current_streak: Option<Streak>,
+	pub openings: Vec<OpeningCount>,
+	pub average_plies: f64,
+}
+
+impl UserStats {
+	/// Aggregates the finished games of `user_id`, which must be ordered from most to least recent.
+	pub fn from_games(user_id: &Uuid, games: &[db::Game]) -> Self {
+		let mut stats = UserStats::default();
+		let mut by_opponent: HashMap<Uuid, Record> = HashMap::new();
+		let mut openings: HashMap<&[String], u32> = HashMap::new();
+		let mut total_plies = 0;
+		let mut streak_broken = false;
+		let standard_start = chess::Board::default().to_string();
+
+		for game in games {
+			// games against oneself say nothing about how a player does
+			if game.white_id == game.black_id {
+				continue;
+			}
+
+			let result = match game.result.as_deref().and_then(|result| result.parse::<GameResult>().ok()) {
+				Some(result) => result,
+				None => continue,
+			};
+
+			let is_white = game.white_id == *user_id;
+			let outcome = match result {
+				GameResult::WhiteCheckmates | GameResult::BlackResigns if is_white => Outcome::Win,
+				GameResult::BlackCheckmates | GameResult::WhiteResigns if !is_white => Outcome::Win,
+				GameResult::Stalemate | GameResult::DrawAccepted | GameResult::DrawDeclared => Outcome::Draw,
+				_ => Outcome::Loss,
+			};
+
+			stats.total.add(outcome);
+			if is_white {
+				stats.by_color.white.add(outcome);
+			} else {
+				stats.by_color.black.add(outcome);
+			}
+
+			let opponent_id = if is_white { game.black_id } else { game.white_id };
+			by_opponent.entry(opponent_id).or_default().add(outcome);
+
+			let time_control = TimeControl::from_columns(
+				game.time_control.as_deref(),
+				game.time_base_ms,
+				game.time_increment_ms,
+				game.days_per_move,
+			);
+			stats
+				.by_time_control
+				.entry(RatingCategory::from(time_control.as_ref()).into())
+				.or_default()
+				.add(outcome);
+
+			let termination = game
+				.termination
+				.as_deref()
+				.and_then(|termination| termination.parse().ok())
+				.unwrap_or_else(|| Termination::from(result));
+			stats.by_termination.entry(termination.into()).or_default().add(outcome);
+
+			match &mut stats.current_streak {
+				Some(streak) if !streak_broken && streak.outcome == outcome => streak.length += 1,
+				Some(_) => streak_broken = true,
+				None => stats.current_streak = Some(Streak { outcome, length: 1 }),
+			}
+
+			if game.start_board == standard_start && game.moves.len() >= OPENING_PLIES {
+				*openings.entry(&game.moves[..OPENING_PLIES]).or_default() += 1;
+			}
+
+			total_plies += game.moves.len();
+		}
+
+		stats.by_opponent = by_opponent
+			.into_iter()
+			.map(|(opponent_id, record)| OpponentRecord { opponent_id, record })
+			.collect();
+		stats.by_opponent.sort_by_key(|opponent| {
+			std::cmp::Reverse(opponent.record.wins + opponent.record.losses + opponent.record.draws)
+		});
+
+		let mut openings = openings.into_iter().collect::<Vec<_>>();
+		openings.sort_by_key(|(_, games)| std::cmp::Reverse(*games));
+		stats.openings = openings
+			.into_iter()
+			.take(TOP_OPENINGS)
+			.map(|(moves, games)| OpeningCount {
+				moves: moves.to_vec(),
+				games,
+			})
+			.collect();
+
+		let counted = stats.total.wins + stats.total.losses + stats.total.draws;
+		if counted > 0 {
+			stats.average_plies = total_plies as f64 / counted as f64;
+		}
+
+		stats
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::NaiveDate;
+
+	fn player() -> Uuid {
+		Uuid::from_u128(1)
+	}
+
+	fn opponent() -> Uuid {
+		Uuid::from_u128(2)
+	}
+
+	fn game(white_id: Uuid, black_id: Uuid, result: Option<&str>, termination: Option<&str>) -> db::Game {
+		let board = chess::Board::default().to_string();
+		let created_at = NaiveDate::from_ymd(2021, 1, 1).and_hms(0, 0, 0);
+
+		db::Game {
+			id: Uuid::from_u128(0),
+			white_id,
+			black_id,
+			board: board.clone(),
+			moves: vec![],
+			result: result.map(str::to_owned),
+			created_at,
+			modified_at: created_at,
+			start_board: board,
+			time_control: None,
+			time_base_ms: None,
+			time_increment_ms: None,
+			days_per_move: None,
+			white_clock_ms: None,
+			black_clock_ms: None,
+			turn_started_at: created_at,
+			termination: termination.map(str::to_owned),
+			halfmove_clock: 0,
+			position_hashes: vec![],
+			draw_offered_by: None,
+			white_draw_offer_ply: None,
+			black_draw_offer_ply: None,
+			takeback_requested_by: None,
+			rated: true,
+		}
+	}
+
+	fn outcome_as(is_white: bool, result: &str) -> Option<Outcome> {
+		let game = if is_white {
+			game(player(), opponent(), Some(result), None)
+		} else {
+			game(opponent(), player(), Some(result), None)
+		};
+		UserStats::from_games(&player(), &[game]).current_streak.map(|streak| streak.outcome)
+	}
+
+	#[test]
+	fn maps_results_to_outcomes_for_each_colour() {
+		for (result, as_white, as_black) in &[
+			("WhiteCheckmates", Outcome::Win, Outcome::Loss),
+			("BlackResigns", Outcome::Win, Outcome::Loss),
+			("BlackCheckmates", Outcome::Loss, Outcome::Win),
+			("WhiteResigns", Outcome::Loss, Outcome::Win),
+			("Stalemate", Outcome::Draw, Outcome::Draw),
+			("DrawAccepted", Outcome::Draw, Outcome::Draw),
+			("DrawDeclared", Outcome::Draw, Outcome::Draw),
+		] {
+			assert_eq!(outcome_as(true, result), Some(*as_white), "{} as white", result);
+			assert_eq!(outcome_as(false, result), Some(*as_black), "{} as black", result);
+		}
+	}
+
+	#[test]
+	fn counts_records_by_colour_and_termination() {
+		let games = [
+			game(player(), opponent(), Some("BlackResigns"), Some("Timeout")),
+			game(opponent(), player(), Some("BlackCheckmates"), Some("Checkmate")),
+			game(opponent(), player(), Some("DrawAccepted"), None),
+		];
+		let stats = UserStats::from_games(&player(), &games);
+
+		assert_eq!((stats.total.wins, stats.total.losses, stats.total.draws), (2, 0, 1));
+		assert_eq!(stats.by_color.white.wins, 1);
+		assert_eq!((stats.by_color.black.wins, stats.by_color.black.draws), (1, 1));
+		assert_eq!(stats.by_termination["Timeout"].wins, 1);
+		assert_eq!(stats.by_termination["Checkmate"].wins, 1);
+		// games without a stored termination fall back to the one their result implies
+		assert_eq!(stats.by_termination["DrawAgreed"].draws, 1);
+		assert_eq!(stats.by_opponent.len(), 1);
+		assert_eq!(stats.by_opponent[0].opponent_id, opponent());
+	}
+
+	#[test]
+	fn skips_unfinished_games_and_games_against_oneself() {
+		let games = [
+			game(player(), opponent(), None, None),
+			game(player(), player(), Some("WhiteCheckmates"), Some("Checkmate")),
+		];
+		let stats = UserStats::from_games(&player(), &games);
+
+		assert_eq!((stats.total.wins, stats.total.losses, stats.total.draws), (0, 0, 0));
+		assert!(stats.current_streak.is_none());
+		assert_eq!(stats.average_plies, 0.0);
+	}
+
+	#[test]
+	fn streak_counts_matching_outcomes_from_the_most_recent_game() {
+		let games = [
+			game(player(), opponent(), Some("WhiteCheckmates"), None),
+			game(opponent(), player(), Some("WhiteResigns"), None),
+			game(player(), opponent(), Some("BlackCheckmates"), None),
+			game(player(), opponent(), Some("WhiteCheckmates"), None),
+		];
+		let streak = UserStats::from_games(&player(), &games).current_streak.unwrap();
+
+		assert_eq!(streak.outcome, Outcome::Win);
+		assert_eq!(streak.length, 2);
+	}
+
+	#[test]
+	fn streak_stops_at_the_first_different_outcome() {
+		let games = [
+			game(player(), opponent(), Some("DrawAccepted"), None),
+			game(player(), opponent(), Some("WhiteCheckmates"), None),
+			game(player(), opponent(), Some("DrawAccepted"), None),
+		];
+		let streak = UserStats::from_games(&player(), &games).current_streak.unwrap();
+
+		assert_eq!(streak.outcome, Outcome::Draw);
+		assert_eq!(streak.length, 1);
+	}
+}
@@ -1,7 +1,8 @@
+use super::rating::UserRating;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use sqlx::{types::Uuid, FromRow};
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr};
 use strum::{EnumString, IntoStaticStr};
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -10,6 +11,11 @@ pub struct User {
 	pub id: Uuid,
 	pub created_at: NaiveDateTime,
 	pub modified_at: NaiveDateTime,
+	/// How many unfinished games the user may have at once.
+	pub max_games: i32,
+	/// The game used when a request names the `default` game instead of a game ID.
+	#[serde(with = "crate::serde::option_uuid")]
+	pub default_game_id: Option<Uuid>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, IntoStaticStr, EnumString)]
@@ -30,12 +36,29 @@ pub struct UserWithAccounts {
 	#[serde(with = "crate::serde::uuid")]
 	pub id: Uuid,
 	pub accounts: Vec<UserAccount>,
+	pub ratings: Vec<UserRating>,
 }
 
 impl UserWithAccounts {
+	/// The identifier to show for this user outside of the API: their first linked account, falling back to the user ID.
+	pub fn account_id(&self) -> String {
+		self.accounts
+			.first()
+			.map(|account| account.account_id.clone())
+			.unwrap_or_else(|| self.id.to_string())
+	}
+
+	/// The user without their linked accounts, for showing to people who aren't playing with them.
+	pub fn redacted(self) -> Self {
+		Self {
+			accounts: vec![],
+			..self
+		}
+	}
+
 	pub async fn fetch<'exec, E>(id: &Uuid, conn: E) -> Result<Self, sqlx::Error>
 	where
-		E: sqlx::Executor<'exec, Database = sqlx::Postgres>,
+		E: sqlx::Executor<'exec, Database = sqlx::Postgres> + Copy,
 	{
 		let accounts = sqlx::query!("select * from user_accounts where user_id = $1", id)
 			.fetch_all(conn)
@@ -54,6 +77,37 @@ impl UserWithAccounts {
 					})
 				})
 				.collect::<Result<_, sqlx::Error>>()?,
+			ratings: UserRating::fetch_all(id, conn).await?,
 		})
 	}
+
+	/// Like [`UserWithAccounts::fetch`] for every one of `ids`, in the same order, with one query for all their accounts
+	/// and another for all their ratings.
+	pub async fn fetch_many<'exec, E>(ids: &[Uuid], conn: E) -> Result<Vec<Self>, sqlx::Error>
+	where
+		E: sqlx::Executor<'exec, Database = sqlx::Postgres> + Copy,
+	{
+		let mut accounts: HashMap<Uuid, Vec<UserAccount>> = HashMap::new();
+		for account in sqlx::query!("select * from user_accounts where user_id = any($1)", ids)
+			.fetch_all(conn)
+			.await?
+		{
+			accounts.entry(account.user_id).or_default().push(UserAccount {
+				user_id: account.user_id,
+				account_id: account.account_id,
+				account_type: AccountType::from_str(&account.account_type)
+					.map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+			});
+		}
+		let mut ratings = UserRating::fetch_all_for(ids, conn).await?;
+
+		Ok(ids
+			.iter()
+			.map(|id| UserWithAccounts {
+				id: id.clone(),
+				accounts: accounts.remove(id).unwrap_or_default(),
+				ratings: ratings.remove(id).unwrap_or_default(),
+			})
+			.collect())
+	}
 }
@@ -0,0 +1,15 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Uuid, FromRow};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Webhook {
+	#[serde(with = "crate::serde::uuid")]
+	pub id: Uuid,
+	#[serde(with = "crate::serde::uuid")]
+	pub user_id: Uuid,
+	pub url: String,
+	/// Key for the HMAC-SHA256 signature sent with every delivery.
+	pub secret: String,
+	pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,255 @@
+use crate::models::r#move::{EndOfGameState, SANChessMove};
+
+mod parse;
+mod write;
+
+pub use parse::PgnError;
+
+/// The tags every exported game must carry, in the order they must be written.
+pub const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// A single game in Portable Game Notation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pgn {
+	pub tags: Vec<(String, String)>,
+	pub moves: MoveText,
+	pub result: EndOfGameState,
+}
+
+/// A line of play: the main line of a game or any of its variations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MoveText {
+	/// Comments preceding the first move of the line.
+	pub comments: Vec<String>,
+	pub plies: Vec<Ply>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ply {
+	pub san: SANChessMove,
+	/// Numeric annotation glyphs; suffix annotations (`!`, `?!`, ...) are stored as their NAG equivalents.
+	pub nags: Vec<u8>,
+	pub comments: Vec<String>,
+	/// Alternatives to this ply, each starting from the position before it was played.
+	pub variations: Vec<MoveText>,
+}
+
+impl Ply {
+	pub fn new(san: SANChessMove) -> Self {
+		Self {
+			san,
+			nags: vec![],
+			comments: vec![],
+			variations: vec![],
+		}
+	}
+}
+
+impl Pgn {
+	pub fn new(result: EndOfGameState) -> Self {
+		Self {
+			tags: vec![],
+			moves: MoveText::default(),
+			result,
+		}
+	}
+
+	pub fn tag(&self, name: &str) -> Option<&str> {
+		self.tags
+			.iter()
+			.find(|(tag, _)| tag == name)
+			.map(|(_, value)| value.as_str())
+	}
+
+	pub fn set_tag(&mut self, name: impl Into<String>, value: impl Into<String>) -> &mut Self {
+		let name = name.into();
+		let value = value.into();
+
+		match self.tags.iter_mut().find(|(tag, _)| *tag == name) {
+			Some((_, existing)) => *existing = value,
+			None => self.tags.push((name, value)),
+		}
+
+		self
+	}
+
+	/// The number of plies played before the first move of the main line, taken from the `FEN` tag.
+	pub fn starting_ply(&self) -> usize {
+		let mut fields = match self.tag("FEN") {
+			Some(fen) => fen.split_whitespace().skip(1),
+			None => return 0,
+		};
+
+		let black_to_move = fields.next() == Some("b");
+		let fullmove = fields
+			.nth(3)
+			.and_then(|n| n.parse::<usize>().ok())
+			.unwrap_or(1)
+			.max(1);
+
+		(fullmove - 1) * 2 + black_to_move as usize
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn ply(san: &str) -> Ply {
+		Ply::new(san.parse().unwrap())
+	}
+
+	fn line(sans: &[&str]) -> MoveText {
+		MoveText {
+			comments: vec![],
+			plies: sans.iter().copied().map(ply).collect(),
+		}
+	}
+
+	/// A game with the whole seven tag roster, which the writer always emits, followed by `extra` tags.
+	fn game(result: EndOfGameState, extra: &[(&str, &str)], moves: MoveText) -> Pgn {
+		let mut pgn = Pgn::new(result.clone());
+		pgn.set_tag("Event", "Casual game")
+			.set_tag("Site", "?")
+			.set_tag("Date", "2021.01.12")
+			.set_tag("Round", "?")
+			.set_tag("White", "white")
+			.set_tag("Black", "black")
+			.set_tag("Result", result.to_string());
+
+		for (name, value) in extra {
+			pgn.set_tag(*name, *value);
+		}
+
+		pgn.moves = moves;
+		pgn
+	}
+
+	fn round_trip(pgn: &Pgn) -> Pgn {
+		pgn.to_string().parse().unwrap()
+	}
+
+	#[test]
+	fn round_trips_tag_pairs() {
+		let pgn = game(
+			EndOfGameState::WhiteWins,
+			&[("WhiteElo", "1500"), ("TimeControl", "300+2"), ("Annotator", "")],
+			line(&["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#"]),
+		);
+
+		assert_eq!(round_trip(&pgn), pgn);
+	}
+
+	#[test]
+	fn round_trips_escaped_strings() {
+		let pgn = game(
+			EndOfGameState::None,
+			&[("Event", r#"The "Immortal" Game"#), ("Site", r"C:\chess\games")],
+			line(&["e4"]),
+		);
+
+		let written = pgn.to_string();
+		assert!(written.contains(r#"[Event "The \"Immortal\" Game"]"#));
+		assert!(written.contains(r#"[Site "C:\\chess\\games"]"#));
+		assert_eq!(round_trip(&pgn), pgn);
+	}
+
+	#[test]
+	fn round_trips_comments() {
+		let mut moves = line(&["e4", "e5", "Nf3"]);
+		moves.comments.push("a classical opening".to_owned());
+		moves.plies[0].comments.push("best by test".to_owned());
+		moves.plies[1].comments.extend(vec!["symmetrical".to_owned(), "solid".to_owned()]);
+
+		let pgn = game(EndOfGameState::None, &[], moves);
+		assert_eq!(round_trip(&pgn), pgn);
+	}
+
+	#[test]
+	fn round_trips_nested_variations() {
+		let mut inner = line(&["Nc6"]);
+		inner.plies[0].comments.push("transposing".to_owned());
+
+		let mut variation = line(&["c5", "Nf3", "d6"]);
+		variation.plies[1].variations.push(inner);
+
+		let mut moves = line(&["e4", "e5", "Nf3", "Nc6"]);
+		moves.plies[1].variations.push(variation);
+		moves.plies[1].variations.push(line(&["e6"]));
+		moves.plies[2].variations.push(MoveText::default());
+
+		let pgn = game(EndOfGameState::Draw, &[], moves);
+		assert_eq!(round_trip(&pgn), pgn);
+	}
+
+	#[test]
+	fn round_trips_nags() {
+		let mut moves = line(&["e4", "e5", "f4", "exf4"]);
+		moves.plies[0].nags.push(1);
+		moves.plies[2].nags.extend(vec![5, 14]);
+		moves.plies[3].nags.push(146);
+
+		let pgn = game(EndOfGameState::None, &[], moves);
+		assert_eq!(round_trip(&pgn), pgn);
+	}
+
+	#[test]
+	fn round_trips_result_tokens() {
+		for result in &[
+			EndOfGameState::WhiteWins,
+			EndOfGameState::BlackWins,
+			EndOfGameState::Draw,
+			EndOfGameState::None,
+		] {
+			let pgn = game(result.clone(), &[], line(&["d4", "d5"]));
+			assert!(pgn.to_string().trim_end().ends_with(&result.to_string()));
+			assert_eq!(round_trip(&pgn), pgn);
+		}
+	}
+
+	#[test]
+	fn round_trips_games_from_a_position() {
+		let pgn = game(
+			EndOfGameState::None,
+			&[("SetUp", "1"), ("FEN", "4k3/8/8/8/8/8/4P3/4K3 b - - 0 12")],
+			line(&["Kd7", "e4", "Ke6"]),
+		);
+
+		assert!(pgn.to_string().contains("12... Kd7 13. e4 Ke6"));
+		assert_eq!(round_trip(&pgn), pgn);
+	}
+
+	#[test]
+	fn parses_suffix_annotations_and_rest_of_line_comments() {
+		let pgn: Pgn = "[Event \"?\"]\n\n1. e4! e5?! ; king's pawn\n2. Nf3 {develops} (2. f4 $6) 2... Nc6 *\n"
+			.parse()
+			.unwrap();
+
+		assert_eq!(pgn.tag("Event"), Some("?"));
+		assert_eq!(pgn.result, EndOfGameState::None);
+		assert_eq!(pgn.moves.plies.len(), 4);
+		assert_eq!(pgn.moves.plies[0].nags, vec![1]);
+		assert_eq!(pgn.moves.plies[1].nags, vec![6]);
+		assert_eq!(pgn.moves.plies[1].comments, vec!["king's pawn"]);
+		assert_eq!(pgn.moves.plies[2].comments, vec!["develops"]);
+		assert_eq!(pgn.moves.plies[2].variations, vec![{
+			let mut variation = line(&["f4"]);
+			variation.plies[0].nags.push(6);
+			variation
+		}]);
+	}
+
+	#[test]
+	fn rejects_missing_result() {
+		assert!("1. e4 e5".parse::<Pgn>().is_err());
+	}
+
+	#[test]
+	fn wraps_long_move_text() {
+		let sans = ["Nf3", "Nf6", "Ng1", "Ng8"].iter().cycle().take(60).copied().collect::<Vec<_>>();
+		let pgn = game(EndOfGameState::Draw, &[], line(&sans));
+
+		assert!(pgn.to_string().lines().all(|line| line.chars().count() <= 79));
+		assert_eq!(round_trip(&pgn), pgn);
+	}
+}
@@ -0,0 +1,166 @@
+use super::{MoveText, Pgn, Ply};
+use crate::models::r#move::{EndOfGameState, SANChessMove};
+use itertools::{Either, Itertools};
+use nom::{
+	branch::alt,
+	bytes::complete::{escaped_transform, is_not, tag, take_till, take_while1},
+	character::complete::{char, digit1, multispace0, not_line_ending},
+	combinator::{all_consuming, map, map_opt, map_res, opt, value, verify},
+	error::{convert_error, VerboseError},
+	multi::{many0, many1},
+	sequence::{delimited, pair, preceded, terminated, tuple},
+	Err, IResult,
+};
+use std::str::FromStr;
+
+type ParseResult<'a, T> = IResult<&'a str, T, VerboseError<&'a str>>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PgnError {
+	#[error("Parse error: {0}")]
+	Parse(String),
+}
+
+fn ws(input: &str) -> ParseResult<&str> {
+	multispace0(input)
+}
+
+fn parse_string(input: &str) -> ParseResult<String> {
+	delimited(
+		char('"'),
+		map(
+			opt(escaped_transform(
+				is_not("\\\""),
+				'\\',
+				alt((value("\\", tag("\\")), value("\"", tag("\"")))),
+			)),
+			Option::unwrap_or_default,
+		),
+		char('"'),
+	)(input)
+}
+
+fn parse_tag_pair(input: &str) -> ParseResult<(String, String)> {
+	delimited(
+		pair(char('['), ws),
+		tuple((
+			terminated(
+				map(take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_'), String::from),
+				ws,
+			),
+			terminated(parse_string, ws),
+		)),
+		char(']'),
+	)(input)
+}
+
+fn parse_comment(input: &str) -> ParseResult<String> {
+	map(
+		alt((
+			delimited(char('{'), take_till(|c: char| c == '}'), char('}')),
+			preceded(char(';'), not_line_ending),
+		)),
+		|comment: &str| comment.trim().to_owned(),
+	)(input)
+}
+
+fn parse_nag(input: &str) -> ParseResult<u8> {
+	alt((
+		preceded(char('$'), map_res(digit1, |n: &str| n.parse())),
+		value(3, tag("!!")),
+		value(4, tag("??")),
+		value(5, tag("!?")),
+		value(6, tag("?!")),
+		value(1, tag("!")),
+		value(2, tag("?")),
+	))(input)
+}
+
+fn parse_move_number(input: &str) -> ParseResult<&str> {
+	terminated(digit1, many1(char('.')))(input)
+}
+
+fn is_san_char(c: char) -> bool {
+	c.is_ascii_alphanumeric() || "+#=:-/*½".contains(c)
+}
+
+fn parse_san(input: &str) -> ParseResult<SANChessMove> {
+	map_res(take_while1(is_san_char), SANChessMove::from_str)(input)
+}
+
+fn parse_termination(input: &str) -> ParseResult<EndOfGameState> {
+	map_opt(parse_san, |mv| match mv {
+		SANChessMove::EOG(state) => Some(state),
+		_ => None,
+	})(input)
+}
+
+fn parse_variation(input: &str) -> ParseResult<MoveText> {
+	delimited(char('('), parse_move_text, char(')'))(input)
+}
+
+fn parse_ply(input: &str) -> ParseResult<Ply> {
+	map(
+		tuple((
+			preceded(
+				opt(pair(parse_move_number, ws)),
+				verify(parse_san, |mv: &SANChessMove| !matches!(mv, SANChessMove::EOG(_))),
+			),
+			many0(preceded(ws, parse_nag)),
+			many0(preceded(
+				ws,
+				alt((
+					map(parse_comment, Either::Left),
+					map(parse_variation, Either::Right),
+				)),
+			)),
+		)),
+		|(san, nags, annotations)| {
+			let (comments, variations) = annotations.into_iter().partition_map(|a| a);
+			Ply {
+				san,
+				nags,
+				comments,
+				variations,
+			}
+		},
+	)(input)
+}
+
+fn parse_move_text(input: &str) -> ParseResult<MoveText> {
+	map(
+		pair(
+			preceded(ws, many0(terminated(parse_comment, ws))),
+			many0(terminated(parse_ply, ws)),
+		),
+		|(comments, plies)| MoveText { comments, plies },
+	)(input)
+}
+
+fn parse_pgn(input: &str) -> ParseResult<Pgn> {
+	all_consuming(map(
+		tuple((
+			preceded(ws, many0(terminated(parse_tag_pair, ws))),
+			parse_move_text,
+			terminated(parse_termination, ws),
+		)),
+		|(tags, moves, result)| Pgn {
+			tags,
+			moves,
+			result,
+		},
+	))(input)
+}
+
+impl FromStr for Pgn {
+	type Err = PgnError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		parse_pgn(s).map(|(_, pgn)| pgn).map_err(|e| {
+			PgnError::Parse(match e {
+				Err::Error(e) | Err::Failure(e) => convert_error(s, e),
+				Err::Incomplete(_) => unreachable!(), // only complete parsers are used
+			})
+		})
+	}
+}
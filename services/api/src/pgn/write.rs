@@ -0,0 +1,108 @@
+use super::{MoveText, Pgn, SEVEN_TAG_ROSTER};
+use crate::models::r#move::PieceStyle;
+use std::fmt::{self, Display, Formatter};
+
+/// Export format keeps movetext lines below 80 columns.
+const MAX_LINE_LENGTH: usize = 79;
+
+fn escape(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn write_tag(f: &mut Formatter<'_>, name: &str, value: &str) -> fmt::Result {
+	writeln!(f, "[{} \"{}\"]", name, escape(value))
+}
+
+fn move_text_tokens(tokens: &mut Vec<String>, text: &MoveText, starting_ply: usize, style: PieceStyle) {
+	tokens.extend(text.comments.iter().map(|comment| format!("{{{}}}", comment)));
+
+	let mut needs_number = true;
+	for (i, ply) in text.plies.iter().enumerate() {
+		let current = starting_ply + i;
+		if current % 2 == 0 {
+			tokens.push(format!("{}.", current / 2 + 1));
+		} else if needs_number {
+			tokens.push(format!("{}...", current / 2 + 1));
+		}
+
+		tokens.push(ply.san.styled(style).to_string());
+		tokens.extend(ply.nags.iter().map(|nag| format!("${}", nag)));
+		tokens.extend(ply.comments.iter().map(|comment| format!("{{{}}}", comment)));
+
+		for variation in &ply.variations {
+			let mut variation_tokens = vec![];
+			move_text_tokens(&mut variation_tokens, variation, current, style);
+
+			match variation_tokens.len() {
+				0 => tokens.push("()".to_owned()),
+				len => {
+					variation_tokens[0].insert(0, '(');
+					variation_tokens[len - 1].push(')');
+					tokens.extend(variation_tokens);
+				}
+			}
+		}
+
+		needs_number = !ply.comments.is_empty() || !ply.variations.is_empty();
+	}
+}
+
+/// A game whose move text is written with a particular piece style.
+pub struct StyledPgn<'a>(&'a Pgn, PieceStyle);
+
+impl Pgn {
+	/// Export format requires English piece letters; other styles are only meant for display.
+	pub fn styled(&self, style: PieceStyle) -> StyledPgn<'_> {
+		StyledPgn(self, style)
+	}
+}
+
+impl Display for Pgn {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.styled(PieceStyle::English))
+	}
+}
+
+impl<'a> Display for StyledPgn<'a> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		let StyledPgn(pgn, style) = *self;
+
+		for name in SEVEN_TAG_ROSTER.iter() {
+			let value = match *name {
+				"Result" => pgn.result.to_string(),
+				"Date" => pgn.tag(name).unwrap_or("????.??.??").to_owned(),
+				_ => pgn.tag(name).unwrap_or("?").to_owned(),
+			};
+
+			write_tag(f, name, &value)?;
+		}
+
+		for (name, value) in &pgn.tags {
+			if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+				write_tag(f, name, value)?;
+			}
+		}
+
+		f.write_str("\n")?;
+
+		let mut tokens = vec![];
+		move_text_tokens(&mut tokens, &pgn.moves, pgn.starting_ply(), style);
+		tokens.push(pgn.result.to_string());
+
+		let mut line_length = 0;
+		for token in tokens {
+			if line_length > 0 && line_length + 1 + token.chars().count() > MAX_LINE_LENGTH {
+				f.write_str("\n")?;
+				line_length = 0;
+			} else if line_length > 0 {
+				f.write_str(" ")?;
+				line_length += 1;
+			}
+
+			f.write_str(&token)?;
+			line_length += token.chars().count();
+		}
+
+		f.write_str("\n")
+	}
+}
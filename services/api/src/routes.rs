@@ -1 +1,3 @@
 pub mod games;
+pub mod webhooks;
+pub mod users;
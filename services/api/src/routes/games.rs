@@ -1,17 +1,25 @@
 use crate::{
 	models::{
+		clock::TimeControl,
 		db,
 		game::Game,
+		r#move::PieceStyle,
 		user::{AccountType, User},
 	},
 	State,
 };
 use chess::Color;
 use serde::Deserialize;
+use sqlx::{postgres::PgPool, types::Uuid, Postgres, Transaction};
 use std::convert::TryInto;
-use tide::Request;
+use tide::{Request, StatusCode};
 
+pub mod challenges;
+pub mod events;
+pub mod import;
 pub mod moves;
+pub mod public;
+pub mod seeks;
 
 #[derive(Debug, Deserialize, Eq, PartialEq)]
 enum CreateGameSide {
@@ -20,8 +28,8 @@ enum CreateGameSide {
 	Random,
 }
 
-impl From<CreateGameSide> for Color {
-	fn from(side: CreateGameSide) -> Self {
+impl From<&CreateGameSide> for Color {
+	fn from(side: &CreateGameSide) -> Self {
 		match side {
 			CreateGameSide::Black => Self::Black,
 			CreateGameSide::White => Self::White,
@@ -42,69 +50,208 @@ impl Default for CreateGameSide {
 	}
 }
 
+#[derive(Debug, Deserialize)]
+struct StyleQuery {
+	#[serde(default)]
+	style: PieceStyle,
+}
+
+impl StyleQuery {
+	fn from_request(req: &Request<State>) -> tide::Result<PieceStyle> {
+		Ok(req.query::<StyleQuery>()?.style)
+	}
+}
+
 #[derive(Debug, Deserialize)]
 struct CreateGame {
 	target_id: String,
 	account_type: Option<AccountType>,
 	#[serde(default)]
 	side: CreateGameSide,
+	time_control: Option<TimeControl>,
+	/// Casual games don't affect either player's rating.
+	#[serde(default)]
+	casual: bool,
 }
 
-pub async fn create_game(mut req: Request<State>) -> tide::Result {
-	let body: CreateGame = req.body_json().await?;
-	let user = req.ext::<User>().unwrap();
+impl CreateGame {
+	/// Resolves the target and returns the `(white_id, black_id)` pair for a game against them.
+	async fn players(&self, user: &User, pool: &PgPool) -> tide::Result<(Uuid, Uuid)> {
+		let target_id = match &self.account_type {
+			Some(account_type) => {
+				let account_type: &str = account_type.into();
+				sqlx::query!(
+					"select id from get_or_create_user($1, $2)",
+					self.target_id,
+					account_type
+				)
+				.fetch_one(pool)
+				.await?
+				.id
+				.unwrap()
+			}
+			None => self.target_id.parse()?,
+		};
+
+		Ok(match Color::from(&self.side) {
+			Color::Black => (target_id, user.id),
+			Color::White => (user.id, target_id),
+		})
+	}
+}
+
+/// Whether either player already has as many unfinished games as their settings allow.
+/// Both players are locked until the transaction ends, so the game must be created in it.
+async fn is_at_game_limit(
+	white_id: &Uuid,
+	black_id: &Uuid,
+	txn: &mut Transaction<'_, Postgres>,
+) -> sqlx::Result<bool> {
+	// locked in a consistent order so that two games between the same players can't deadlock
+	sqlx::query!("select id from users where id in ($1, $2) order by id for update", white_id, black_id)
+		.fetch_all(&mut *txn)
+		.await?;
 
+	Ok(sqlx::query!(
+		r#"select exists (
+	select 1 from users
+	where users.id in ($1, $2)
+		and (select count(*) from games where (white_id = users.id or black_id = users.id) and result is null) >= users.max_games
+) as "at_limit!""#,
+		white_id,
+		black_id
+	)
+	.fetch_one(&mut *txn)
+	.await?
+	.at_limit)
+}
+
+pub async fn list_active_games(req: Request<State>) -> tide::Result {
+	let style = StyleQuery::from_request(&req)?;
+	let user = req.ext::<User>().unwrap();
 	let pool = &req.state().db;
 
-	let target_id = match body.account_type {
-		Some(account_type) => {
-			let account_type: &str = account_type.into();
-			sqlx::query!(
-				"select id from get_or_create_user($1, $2)",
-				body.target_id,
-				account_type
-			)
-			.fetch_one(pool)
-			.await?
-			.id
-			.unwrap()
+	let rows = sqlx::query_as!(
+		db::Game,
+		"select * from games where (white_id = $1 or black_id = $1) and result is null order by modified_at desc",
+		user.id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	let mut games = Vec::with_capacity(rows.len());
+	for row in rows {
+		let mut game: Game = row.try_into()?;
+		game.localize(style)?;
+		games.push(game.with_users(pool).await?);
+	}
+
+	Ok(tide::Body::from_json(&games)?.into())
+}
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+const PGN_CONTENT_TYPE: &str = "application/x-chess-pgn";
+const FEN_CONTENT_TYPE: &str = "application/x-chess-fen";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum GameFormat {
+	Json,
+	Pgn,
+	Fen,
+}
+
+impl GameFormat {
+	/// Picks the format with the highest quality in the `Accept` header, using `default` for wildcards.
+	fn negotiate(req: &Request<State>, default: GameFormat) -> Option<GameFormat> {
+		let accept = match req.header("accept") {
+			Some(accept) => accept.as_str(),
+			None => return Some(default),
+		};
+
+		let mut best: Option<(f32, GameFormat)> = None;
+		for range in accept.split(',') {
+			let mut params = range.split(';');
+			let media_type = params.next().unwrap_or_default().trim();
+			let quality = params
+				.find_map(|param| param.trim().strip_prefix("q="))
+				.and_then(|q| q.parse().ok())
+				.unwrap_or(1.0);
+
+			let format = match media_type {
+				"*/*" | "application/*" => default,
+				JSON_CONTENT_TYPE => GameFormat::Json,
+				PGN_CONTENT_TYPE => GameFormat::Pgn,
+				FEN_CONTENT_TYPE => GameFormat::Fen,
+				_ => continue,
+			};
+
+			if quality > 0.0 && best.map_or(true, |(best_quality, _)| quality > best_quality) {
+				best = Some((quality, format));
+			}
 		}
-		None => body.target_id.parse()?,
-	};
 
-	let (white_id, black_id) = match body.side.into() {
-		Color::Black => (target_id, user.id),
-		Color::White => (user.id, target_id),
+		best.map(|(_, format)| format)
+	}
+}
+
+async fn respond_with_game(req: &Request<State>, default: GameFormat) -> tide::Result {
+	let format = match GameFormat::negotiate(req, default) {
+		Some(format) => format,
+		None => return Ok(tide::Response::new(StatusCode::NotAcceptable)),
 	};
 
-	let is_already_playing = sqlx::query!("select id from games where (white_id = $1 or black_id = $1 or white_id = $2 or black_id = $2) and result is null", white_id, black_id)
-		.fetch_optional(pool)
-		.await?
-		.is_some();
+	let style = StyleQuery::from_request(req)?;
+	let pool = &req.state().db;
+	let user = req.ext::<User>().unwrap();
+	let mut game = req.ext::<Game>().unwrap().clone();
 
-	if is_already_playing {
-		let mut res = tide::Response::new(tide::StatusCode::BadRequest);
-		res.set_body("already playing");
-		return Ok(res);
+	// anyone who isn't playing sees the game as they would on the public route
+	let spectating = game.color_of(user).is_none();
+	if spectating {
+		game.delay(req.state().broadcast_delay_plies)?;
 	}
 
-	let game: Game = sqlx::query_as!(
-		db::Game,
-		"insert into games (white_id, black_id, board) values ($1, $2, $3) returning *",
-		white_id,
-		black_id,
-		chess::Board::default().to_string()
-	)
-	.fetch_one(pool)
-	.await?
-	.try_into()?;
+	let mut res = tide::Response::new(StatusCode::Ok);
+	match format {
+		GameFormat::Json => {
+			game.localize(style)?;
+			let mut game = game.with_users(pool).await?;
+			if spectating {
+				game.white = game.white.redacted();
+				game.black = game.black.redacted();
+			}
+			res.set_body(tide::Body::from_json(&game)?);
+		}
+		GameFormat::Pgn => {
+			let mut pgn = game.to_pgn()?;
+			let mut game = game.with_users(pool).await?;
+			if spectating {
+				game.white = game.white.redacted();
+				game.black = game.black.redacted();
+			}
+			pgn.set_tag("White", game.white.account_id())
+				.set_tag("Black", game.black.account_id());
 
-	let game = game.with_users(pool).await?;
-	Ok(tide::Body::from_json(&game)?.into())
+			res.set_body(pgn.styled(style).to_string());
+			res.set_content_type(PGN_CONTENT_TYPE);
+			res.insert_header(
+				"content-disposition",
+				format!("attachment; filename=\"{}.pgn\"", game.id),
+			);
+		}
+		GameFormat::Fen => {
+			res.set_body(game.board.current_position().to_string());
+			res.set_content_type(FEN_CONTENT_TYPE);
+		}
+	}
+
+	Ok(res)
 }
 
 pub async fn get_game(req: Request<State>) -> tide::Result {
-	let pool = &req.state().db;
-	let game = req.ext::<Game>().unwrap().clone().with_users(pool).await?;
-	Ok(tide::Body::from_json(&game)?.into())
+	respond_with_game(&req, GameFormat::Json).await
+}
+
+pub async fn get_pgn(req: Request<State>) -> tide::Result {
+	respond_with_game(&req, GameFormat::Pgn).await
 }
@@ -0,0 +1,203 @@
+use super::{is_at_game_limit, CreateGame};
+use crate::{
+	hub::GameEventKind,
+	models::{
+		challenge::Challenge,
+		db,
+		game::{color_to_column, Game},
+		user::User,
+	},
+	webhooks, State,
+};
+use chess::Color;
+use serde::Deserialize;
+use sqlx::types::Uuid;
+use std::convert::TryInto;
+use tide::{Request, Response, StatusCode};
+
+#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Direction {
+	Incoming,
+	Outgoing,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChallengeQuery {
+	direction: Option<Direction>,
+}
+
+/// Marks pending challenges that have gone unanswered for too long as expired.
+async fn expire_challenges<'exec, E>(conn: E) -> sqlx::Result<()>
+where
+	E: sqlx::Executor<'exec, Database = sqlx::Postgres>,
+{
+	sqlx::query!("update challenges set status = 'Expired', modified_at = now() where status = 'Pending' and expires_at <= now()")
+		.execute(conn)
+		.await?;
+
+	Ok(())
+}
+
+pub async fn create_challenge(mut req: Request<State>) -> tide::Result {
+	let body: CreateGame = req.body_json().await?;
+	let user = req.ext::<User>().unwrap();
+	let state = req.state();
+
+	let (white_id, black_id) = body.players(user, &state.db).await?;
+	if white_id == black_id {
+		return Ok(tide::Error::from_str(StatusCode::BadRequest, "You can't challenge yourself").into());
+	}
+
+	let (challenger_color, target_id) = if white_id == user.id {
+		(Color::White, black_id)
+	} else {
+		(Color::Black, white_id)
+	};
+
+	let (time_control, time_base_ms, time_increment_ms, days_per_move) = match &body.time_control {
+		Some(time_control) => {
+			let (name, base_ms, increment_ms, days) = time_control.to_columns();
+			(Some(name), base_ms, increment_ms, days)
+		}
+		None => (None, None, None, None),
+	};
+
+	let challenge: Challenge = sqlx::query_as!(
+		db::Challenge,
+		"insert into challenges (challenger_id, target_id, challenger_color, time_control, time_base_ms, time_increment_ms, days_per_move, rated, expires_at)
+values ($1, $2, $3, $4, $5, $6, $7, $8, now() + $9 * interval '1 second') returning *",
+		user.id,
+		target_id,
+		color_to_column(challenger_color),
+		time_control,
+		time_base_ms,
+		time_increment_ms,
+		days_per_move,
+		!body.casual,
+		state.challenge_expiry.as_secs_f64()
+	)
+	.fetch_one(&state.db)
+	.await?
+	.into();
+
+	Ok(tide::Body::from_json(&challenge)?.into())
+}
+
+pub async fn list_challenges(req: Request<State>) -> tide::Result {
+	let direction = req.query::<ChallengeQuery>()?.direction;
+	let user = req.ext::<User>().unwrap();
+	let pool = &req.state().db;
+
+	expire_challenges(pool).await?;
+
+	let challenges = sqlx::query_as!(
+		db::Challenge,
+		"select * from challenges
+where status = 'Pending' and ((target_id = $1 and $2) or (challenger_id = $1 and $3))
+order by created_at",
+		user.id,
+		direction != Some(Direction::Outgoing),
+		direction != Some(Direction::Incoming)
+	)
+	.fetch_all(pool)
+	.await?
+	.into_iter()
+	.map(Challenge::from)
+	.collect::<Vec<_>>();
+
+	Ok(tide::Body::from_json(&challenges)?.into())
+}
+
+pub async fn accept_challenge(req: Request<State>) -> tide::Result {
+	let user = req.ext::<User>().unwrap();
+	let challenge_id = req.param("challenge_id")?.parse::<Uuid>()?;
+	let state = req.state();
+
+	let mut txn = state.db.begin().await?;
+	expire_challenges(&mut txn).await?;
+
+	let challenge: Challenge = match sqlx::query_as!(
+		db::Challenge,
+		"select * from challenges where id = $1 and target_id = $2 and status = 'Pending' for update",
+		challenge_id,
+		user.id
+	)
+	.fetch_optional(&mut txn)
+	.await?
+	{
+		Some(challenge) => challenge.into(),
+		None => return Ok(Response::new(StatusCode::NotFound)),
+	};
+
+	let (white_id, black_id) = challenge.players();
+	if is_at_game_limit(&white_id, &black_id, &mut txn).await? {
+		return Ok(tide::Error::from_str(StatusCode::BadRequest, "too many games in progress").into());
+	}
+
+	let game: Game = db::NewGame {
+		time_control: challenge.time_control,
+		rated: challenge.rated,
+		..db::NewGame::new(white_id, black_id)
+	}
+	.insert(&mut txn)
+	.await?
+	.try_into()?;
+
+	sqlx::query!(
+		"update challenges set status = 'Accepted', game_id = $1, modified_at = now() where id = $2",
+		game.id,
+		challenge.id
+	)
+	.execute(&mut txn)
+	.await?;
+
+	let game = game.with_users(&state.db).await?;
+	webhooks::enqueue(&game, &[GameEventKind::Created], &mut txn).await?;
+	txn.commit().await?;
+
+	state.publish(&game, &[GameEventKind::Created]).await;
+	Ok(tide::Body::from_json(&game)?.into())
+}
+
+pub async fn decline_challenge(req: Request<State>) -> tide::Result {
+	let user = req.ext::<User>().unwrap();
+	let challenge_id = req.param("challenge_id")?.parse::<Uuid>()?;
+	let pool = &req.state().db;
+
+	let challenge = sqlx::query_as!(
+		db::Challenge,
+		"update challenges set status = 'Declined', modified_at = now()
+where id = $1 and target_id = $2 and status = 'Pending' and expires_at > now() returning *",
+		challenge_id,
+		user.id
+	)
+	.fetch_optional(pool)
+	.await?;
+
+	match challenge {
+		Some(challenge) => Ok(tide::Body::from_json(&Challenge::from(challenge))?.into()),
+		None => Ok(Response::new(StatusCode::NotFound)),
+	}
+}
+
+pub async fn cancel_challenge(req: Request<State>) -> tide::Result {
+	let user = req.ext::<User>().unwrap();
+	let challenge_id = req.param("challenge_id")?.parse::<Uuid>()?;
+	let pool = &req.state().db;
+
+	let challenge = sqlx::query_as!(
+		db::Challenge,
+		"update challenges set status = 'Cancelled', modified_at = now()
+where id = $1 and challenger_id = $2 and status = 'Pending' and expires_at > now() returning *",
+		challenge_id,
+		user.id
+	)
+	.fetch_optional(pool)
+	.await?;
+
+	match challenge {
+		Some(challenge) => Ok(tide::Body::from_json(&Challenge::from(challenge))?.into()),
+		None => Ok(Response::new(StatusCode::NotFound)),
+	}
+}
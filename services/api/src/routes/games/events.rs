@@ -0,0 +1,19 @@
+use crate::{models::game::Game, State};
+use tide::{sse::Sender, Request};
+
+/// Streams the game's events live, so only its players may follow it; spectators get the delayed public view instead.
+pub async fn stream_events(req: Request<State>, sender: Sender) -> tide::Result<()> {
+	let game = req.ext::<Game>().unwrap();
+	if game.result.is_some() {
+		return Ok(());
+	}
+
+	let events = req.state().hub.subscribe(game.id);
+
+	while let Ok(event) = events.recv().await {
+		let kind: &str = event.kind.into();
+		sender.send(kind, &*event.data, None).await?;
+	}
+
+	Ok(())
+}
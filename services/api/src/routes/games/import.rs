@@ -0,0 +1,188 @@
+use super::{is_at_game_limit, CreateGame};
+use crate::{
+	hub::GameEventKind,
+	models::{
+		db,
+		draw,
+		game::{Game, Termination},
+		r#move::{EndOfGameState, SANChessMove},
+		user::User,
+	},
+	pgn::Pgn,
+	webhooks, State,
+};
+use chess::{Color, GameResult};
+use serde::Deserialize;
+use sqlx::types::Uuid;
+use std::convert::TryInto;
+use tide::{Request, StatusCode};
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ImportSource {
+	Pgn {
+		pgn: String,
+	},
+	Position {
+		fen: Option<String>,
+		#[serde(default)]
+		moves: Vec<String>,
+	},
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportGame {
+	#[serde(flatten)]
+	opponent: CreateGame,
+	#[serde(flatten)]
+	source: ImportSource,
+}
+
+fn bad_request(message: impl Into<String>) -> tide::Error {
+	tide::Error::from_str(StatusCode::BadRequest, message.into())
+}
+
+pub async fn import_game(mut req: Request<State>) -> tide::Result {
+	let body: ImportGame = req.body_json().await?;
+	let user = req.ext::<User>().unwrap();
+
+	let pool = &req.state().db;
+
+	let (fen, moves, declared_result, declared_termination) = match body.source {
+		ImportSource::Pgn { pgn } => {
+			let pgn: Pgn = pgn.parse().map_err(|e| tide::Error::new(StatusCode::BadRequest, e))?;
+			let moves = pgn.moves.plies.into_iter().map(|ply| ply.san).collect();
+
+			let (mut fen, mut termination) = (None, None);
+			for (name, value) in pgn.tags {
+				match name.as_str() {
+					"FEN" => fen = Some(value),
+					"Termination" => termination = Termination::from_pgn_tag(&value),
+					_ => {}
+				}
+			}
+
+			(fen, moves, pgn.result, termination)
+		}
+		ImportSource::Position { fen, moves } => {
+			let moves = moves
+				.iter()
+				.enumerate()
+				.map(|(ply, san)| {
+					san.parse::<SANChessMove>()
+						.map_err(|e| bad_request(format!("invalid move {} at ply {}: {}", san, ply + 1, e)))
+				})
+				.collect::<Result<Vec<_>, _>>()?;
+
+			(fen, moves, EndOfGameState::None, None)
+		}
+	};
+
+	let (start_board, mut halfmove_clock): (chess::Board, i32) = match &fen {
+		Some(fen) => (
+			fen.parse().map_err(|_| bad_request(format!("invalid FEN: {}", fen)))?,
+			// the board doesn't keep the halfmove clock, so it has to be read from the FEN itself
+			draw::fen_halfmove_clock(fen),
+		),
+		None => (chess::Board::default(), 0),
+	};
+
+	// the start position keeps its halfmove clock so that replaying the moves reaches the same clock
+	let start_fen = draw::fen_with_halfmove_clock(&start_board, halfmove_clock);
+	let mut board = chess::Game::new_with_board(start_board);
+	let mut canonical_moves = Vec::with_capacity(moves.len());
+	let mut position_hashes = vec![draw::position_hash(&start_board)];
+	// the `game_moves` history of each ply, which only gets its players once they're known
+	let mut movers = Vec::with_capacity(moves.len());
+	let mut ucis = Vec::with_capacity(moves.len());
+	let mut fens = Vec::with_capacity(moves.len());
+	for (ply, san) in moves.iter().enumerate() {
+		let position = board.current_position();
+		let board_move = san
+			.resolve(&position)
+			.map_err(|e| bad_request(format!("illegal move {} at ply {}: {}", san, ply + 1, e)))?;
+
+		canonical_moves.push(SANChessMove::from((&position, board_move)).to_string());
+		halfmove_clock = draw::next_halfmove_clock(&position, board_move, halfmove_clock);
+		board.make_move(board_move);
+		position_hashes.push(draw::position_hash(&board.current_position()));
+
+		movers.push(position.side_to_move());
+		ucis.push(board_move.to_string());
+		fens.push(board.current_position().to_string());
+	}
+
+	// a result that isn't evident from the final position is recorded like a resignation or an agreed draw,
+	// and the termination says whether it actually came from a flag falling or an abandoned game
+	let termination = board.result().map(Termination::from);
+	let result = board.result().or(match declared_result {
+		EndOfGameState::WhiteWins => Some(GameResult::BlackResigns),
+		EndOfGameState::BlackWins => Some(GameResult::WhiteResigns),
+		EndOfGameState::Draw => Some(GameResult::DrawAccepted),
+		EndOfGameState::None => None,
+	});
+
+	let (white_id, black_id) = body.opponent.players(user, pool).await?;
+
+	// an unfinished game would carry on without the opponent having agreed to play it
+	if result.is_none() && white_id != black_id {
+		return Ok(bad_request(
+			"unfinished games can only be imported against yourself; challenge your opponent instead",
+		)
+		.into());
+	}
+
+	let mut txn = pool.begin().await?;
+	if result.is_none() && is_at_game_limit(&white_id, &black_id, &mut txn).await? {
+		let mut res = tide::Response::new(StatusCode::BadRequest);
+		res.set_body("too many games in progress");
+		return Ok(res);
+	}
+
+	let game: Game = db::NewGame {
+		white_id,
+		black_id,
+		start_board: start_fen,
+		board: board.current_position().to_string(),
+		moves: canonical_moves,
+		result: result.map(|r| <&str>::from(r).to_owned()),
+		termination: termination
+			.or_else(|| result.map(|r| declared_termination.unwrap_or_else(|| Termination::from(r))))
+			.map(|termination| <&str>::from(termination).to_owned()),
+		time_control: body.opponent.time_control,
+		halfmove_clock,
+		position_hashes,
+		// games played elsewhere don't count towards ratings here
+		rated: false,
+	}
+	.insert(&mut txn)
+	.await?
+	.try_into()?;
+
+	let plies: Vec<i32> = (1..=game.moves.len() as i32).collect();
+	let user_ids: Vec<Uuid> = movers
+		.into_iter()
+		.map(|color| if color == Color::White { white_id } else { black_id })
+		.collect();
+	// the moves were played elsewhere, so how long each one took isn't known
+	sqlx::query!(
+		"insert into game_moves (game_id, ply, san, uci, fen, user_id, time_spent_ms)
+select $1, ply, san, uci, fen, user_id, 0
+from unnest($2::int4[], $3::varchar[], $4::varchar[], $5::varchar[], $6::uuid[]) as history(ply, san, uci, fen, user_id)",
+		game.id,
+		plies,
+		game.moves,
+		ucis,
+		fens,
+		user_ids
+	)
+	.execute(&mut txn)
+	.await?;
+
+	let game = game.with_users(pool).await?;
+	webhooks::enqueue(&game, &[GameEventKind::Created], &mut txn).await?;
+	txn.commit().await?;
+
+	req.state().publish(&game, &[GameEventKind::Created]).await;
+	Ok(tide::Body::from_json(&game)?.into())
+}
@@ -1,72 +1,314 @@
-use crate::{State, models::game::{Game, UserColor}};
-use chess::{ChessMove, Color};
+use super::StyleQuery;
+use crate::{State, hub::GameEventKind, webhooks, models::{clock::timeout_result, db, draw, game::{color_to_column, Game, Termination, UserColor}, history::GameMove, rating, r#move::{Notation, SANChessMove}, user::User}};
+use chess::{Color, GameResult};
+use chrono::Utc;
 use serde::Deserialize;
+use std::convert::TryInto;
 use tide::{Request, StatusCode};
 
+#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+enum MakeMove {
+	Detect(String),
+	Explicit { notation: Notation, r#move: String },
+}
+
 #[derive(Debug, Deserialize, Eq, PartialEq)]
 #[serde(tag = "action", content = "data")]
 enum MoveRequest {
-	MakeMove(String),
+	MakeMove(MakeMove),
 	AcceptDraw,
+	DeclineDraw,
 	OfferDraw,
 	DeclareDraw,
 	Resign,
+	RequestTakeback,
+	AcceptTakeback,
+	DeclineTakeback,
+}
+
+impl From<&MoveRequest> for GameEventKind {
+	fn from(request: &MoveRequest) -> Self {
+		match request {
+			MoveRequest::MakeMove(_) => GameEventKind::Move,
+			MoveRequest::AcceptDraw => GameEventKind::DrawAccepted,
+			MoveRequest::DeclineDraw => GameEventKind::DrawDeclined,
+			MoveRequest::OfferDraw => GameEventKind::DrawOffer,
+			MoveRequest::DeclareDraw => GameEventKind::DrawClaimed,
+			MoveRequest::Resign => GameEventKind::Resign,
+			MoveRequest::RequestTakeback => GameEventKind::TakebackRequest,
+			MoveRequest::AcceptTakeback => GameEventKind::Takeback,
+			MoveRequest::DeclineTakeback => GameEventKind::TakebackDeclined,
+		}
+	}
 }
 
 pub async fn make_move(mut req: Request<State>) -> tide::Result {
 	let move_request: MoveRequest = req.body_json().await?;
-	let mut game = req.ext::<Game>().unwrap().clone();
+	let style = StyleQuery::from_request(&req)?;
+	let game_id = req.ext::<Game>().unwrap().id;
 	let user_color = req.ext::<UserColor>().unwrap().clone();
-
-	let is_users_turn = user_color == game.side_to_move;
+	let user = req.ext::<User>().unwrap();
 
 	let pool = &req.state().db;
 	let mut txn = pool.begin().await?;
 
+	// the game loaded by the middleware may already be out of date, so it's loaded again and locked until this request
+	// is done, keeping other requests and the adjudicator from changing it in the meantime
+	let mut game: Game = sqlx::query_as!(db::Game, "select * from games where id = $1 for update", game_id)
+		.fetch_one(&mut txn)
+		.await?
+		.try_into()?;
+
+	if game.result.is_some() {
+		return Ok(tide::Error::from_str(StatusCode::BadRequest, "Game is over").into());
+	}
+
+	let is_users_turn = user_color == game.side_to_move;
+
+	let now = Utc::now().naive_utc();
+	let side_to_move = game.side_to_move;
+	let ply = game.moves.len();
+	let flag_fell = match &game.clock {
+		Some(clock) => clock.remaining(side_to_move, now) <= 0,
+		None => false,
+	};
+
+	let mut events = vec![];
+	if !flag_fell {
+		events.push(GameEventKind::from(&move_request));
+	}
+
+	let mut drawn = None;
 	match move_request {
-		MoveRequest::MakeMove(san) if is_users_turn => {
-			let board_move = ChessMove::from_san(&game.board.current_position(), &san)?;
+		// the side to move lost on time before this request was made, so it can't change anything
+		_ if flag_fell => {
+			if let Some(clock) = &mut game.clock {
+				clock.punch(side_to_move, now);
+			}
+		}
+		MoveRequest::MakeMove(mv) if is_users_turn => {
+			let (notation, text) = match mv {
+				MakeMove::Detect(text) => (Notation::detect(&text), text),
+				MakeMove::Explicit { notation, r#move } => (notation, r#move),
+			};
 
+			let position = game.board.current_position();
+			let board_move = notation
+				.parse(&text, &position)
+				.map_err(|e| tide::Error::new(StatusCode::BadRequest, e))?
+				.resolve(&position)
+				.map_err(|e| tide::Error::new(StatusCode::BadRequest, e))?;
+			let san = SANChessMove::from((&position, board_move)).to_string();
+
+			game.halfmove_clock = draw::next_halfmove_clock(&position, board_move, game.halfmove_clock);
 			game.board.make_move(board_move);
 			game.moves.push(san.clone());
 
+			game.position_hashes.push(draw::position_hash(&game.board.current_position()));
+
+			if let Some(clock) = &mut game.clock {
+				clock.punch(side_to_move, now);
+			}
+
+			game.draw_offers.expire(side_to_move);
+			game.takeback_requested_by = None;
+
+			// every hash is written, rather than appending the new one, so games from before draw tracking get the full history
 			sqlx::query!(
-				"update games set moves = array_append(moves, $1) where id = $2",
+				"update games set moves = array_append(moves, $1), position_hashes = $2, halfmove_clock = $3 where id = $4",
 				san,
+				game.position_hashes,
+				game.halfmove_clock,
 				game.id
 			)
 			.execute(&mut txn)
 			.await?;
+
+			sqlx::query!(
+				"insert into game_moves (game_id, ply, san, uci, fen, user_id, time_spent_ms)
+values ($1, $2, $3, $4, $5, $6, (extract(epoch from now() - coalesce(
+	(select max(created_at) from game_moves where game_id = $1),
+	(select created_at from games where id = $1)
+)) * 1000)::int8)",
+				game.id,
+				game.moves.len() as i32,
+				san,
+				board_move.to_string(),
+				game.board.current_position().to_string(),
+				user.id
+			)
+			.execute(&mut txn)
+			.await?;
 		}
-		MoveRequest::AcceptDraw if is_users_turn => {
-			game.board.accept_draw();
+		MoveRequest::AcceptDraw => {
+			game.draw_offers
+				.respond(user_color.into())
+				.map_err(|e| tide::Error::new(StatusCode::BadRequest, e))?;
+			drawn = Some((GameResult::DrawAccepted, Termination::DrawAgreed));
 		}
-		MoveRequest::OfferDraw => {
-			game.board.offer_draw(Option::<Color>::from(user_color).unwrap_or(Color::Black));
+		MoveRequest::DeclineDraw => {
+			game.draw_offers
+				.respond(user_color.into())
+				.map_err(|e| tide::Error::new(StatusCode::BadRequest, e))?;
 		}
-		MoveRequest::DeclareDraw => {
-			game.board.declare_draw();
+		MoveRequest::OfferDraw => {
+			let by = Option::<Color>::from(user_color).unwrap_or(side_to_move);
+			game.draw_offers
+				.offer(by, ply as i32)
+				.map_err(|e| tide::Error::new(StatusCode::BadRequest, e))?;
 		}
+		MoveRequest::DeclareDraw => match draw::claimable(&game) {
+			Some(termination) => drawn = Some((GameResult::DrawDeclared, termination)),
+			None => {
+				return Ok(tide::Error::from_str(
+					StatusCode::BadRequest,
+					"No draw can be claimed: the position hasn't occurred three times and a capture or pawn move was made within the last fifty moves",
+				)
+				.into())
+			}
+		},
 		MoveRequest::Resign => {
 			game.board.resign(Option::<Color>::from(user_color).unwrap_or(Color::Black));
 		}
+		MoveRequest::RequestTakeback => {
+			if game.moves.is_empty() {
+				return Ok(tide::Error::from_str(StatusCode::BadRequest, "There are no moves to take back").into());
+			}
+
+			if game.takeback_requested_by.is_some() {
+				return Ok(tide::Error::from_str(StatusCode::BadRequest, "A takeback is already pending").into());
+			}
+
+			let requested_by = Option::<Color>::from(user_color).unwrap_or(!side_to_move);
+			// with the requester to move and a single ply played, that ply was the opponent's
+			if requested_by == side_to_move && ply < 2 {
+				return Ok(tide::Error::from_str(StatusCode::BadRequest, "You haven't made a move to take back").into());
+			}
+
+			game.takeback_requested_by = Some(requested_by);
+		}
+		MoveRequest::AcceptTakeback | MoveRequest::DeclineTakeback => {
+			let requested_by = match game.takeback_requested_by {
+				Some(requested_by) if Option::<Color>::from(user_color) != Some(requested_by) => requested_by,
+				_ => {
+					return Ok(tide::Error::from_str(
+						StatusCode::BadRequest,
+						"There is no takeback request from your opponent to respond to",
+					)
+					.into())
+				}
+			};
+			game.takeback_requested_by = None;
+
+			if move_request == MoveRequest::AcceptTakeback {
+				// the opponent's reply is undone as well, so that it's the requester's turn again,
+				// but nothing is undone beyond the requester's own last move
+				let plies = match (requested_by == side_to_move, ply) {
+					(true, ply) if ply >= 2 => 2,
+					(true, _) => 0,
+					(false, _) => 1,
+				};
+				game.moves.truncate(ply.saturating_sub(plies));
+				game.replay()?;
+				game.draw_offers.pending = None;
+
+				if let Some(clock) = &mut game.clock {
+					clock.turn_started_at = now;
+				}
+
+				sqlx::query!(
+					"update games set moves = $1, position_hashes = $2, halfmove_clock = $3 where id = $4",
+					game.moves,
+					game.position_hashes,
+					game.halfmove_clock,
+					game.id
+				)
+				.execute(&mut txn)
+				.await?;
+
+				sqlx::query!(
+					"update game_moves set taken_back_at = now() where game_id = $1 and ply > $2 and taken_back_at is null",
+					game.id,
+					game.moves.len() as i32
+				)
+				.execute(&mut txn)
+				.await?;
+			}
+		}
 		_ => return Ok(tide::Error::from_str(StatusCode::BadRequest, "Not your turn").into()),
 	}
 
-	game.reload();
+	let position = game.board.current_position();
+	game.reload()?;
 
-	let result: Option<&str> = game.board.result().map(|r| r.into());
+	if flag_fell {
+		game.result = Some(timeout_result(&position, side_to_move));
+		game.termination = Some(Termination::Timeout);
+	} else if game.result.is_none() {
+		let automatic = || draw::automatic(&game).map(|termination| (GameResult::DrawDeclared, termination));
+		if let Some((result, termination)) = drawn.or_else(automatic) {
+			game.result = Some(result);
+			game.termination = Some(termination);
+		}
+	}
+
+	if game.result.is_some() {
+		game.draw_offers.pending = None;
+		events.push(GameEventKind::End);
+	}
+
+	let result: Option<&str> = game.result.map(|r| r.into());
+	let termination: Option<&str> = game.termination.map(|t| t.into());
+	let turn_started_at = match &game.clock {
+		Some(clock) => Some(clock.turn_started_at),
+		None if game.moves.len() != ply => Some(now),
+		None => None,
+	};
 	sqlx::query!(
-		"update games set board = $1, result = $2, modified_at = now() where id = $3",
-		game.board.current_position().to_string(),
+		"update games set board = $1, result = $2, termination = $3, white_clock_ms = $4, black_clock_ms = $5, turn_started_at = coalesce($6, turn_started_at),
+	draw_offered_by = $7, white_draw_offer_ply = $8, black_draw_offer_ply = $9, takeback_requested_by = $10, modified_at = now() where id = $11",
+		position.to_string(),
 		result,
+		termination,
+		game.clock.as_ref().map(|clock| clock.white_ms),
+		game.clock.as_ref().map(|clock| clock.black_ms),
+		turn_started_at,
+		game.draw_offers.pending_column(),
+		game.draw_offers.white_ply,
+		game.draw_offers.black_ply,
+		game.takeback_requested_by.map(color_to_column),
 		game.id
 	)
 	.execute(&mut txn)
 	.await?;
 
+	rating::rate_game(&game, &mut txn).await?;
+
+	let mut published = game.clone();
+	published.pgn = (&published.to_pgn()?).into();
+	let published = published.with_users(pool).await?;
+	webhooks::enqueue(&published, &events, &mut txn).await?;
 	txn.commit().await?;
 
+	req.state().publish(&published, &events).await;
+
+	game.localize(style)?;
 	let game = game.with_users(pool).await?;
 	Ok(tide::Body::from_json(&game)?.into())
 }
+
+pub async fn list_moves(req: Request<State>) -> tide::Result {
+	let game = req.ext::<Game>().unwrap();
+	let pool = &req.state().db;
+
+	let moves = sqlx::query_as!(
+		GameMove,
+		"select * from game_moves where game_id = $1 order by ply, created_at",
+		game.id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	Ok(tide::Body::from_json(&moves)?.into())
+}
@@ -0,0 +1,30 @@
+use super::StyleQuery;
+use crate::{
+	models::{db, game::Game},
+	State,
+};
+use sqlx::types::Uuid;
+use std::convert::TryInto;
+use tide::{Request, Response, StatusCode};
+
+pub async fn get_game(req: Request<State>) -> tide::Result {
+	let game_id = req.param("game_id")?.parse::<Uuid>()?;
+	let style = StyleQuery::from_request(&req)?;
+	let state = req.state();
+
+	let mut game: Game = match sqlx::query_as!(db::Game, "select * from games where id = $1", game_id)
+		.fetch_optional(&state.db)
+		.await?
+	{
+		Some(game) => game.try_into()?,
+		None => return Ok(Response::new(StatusCode::NotFound)),
+	};
+
+	game.delay(state.broadcast_delay_plies)?;
+	game.localize(style)?;
+	let mut game = game.with_users(&state.db).await?;
+	game.white = game.white.redacted();
+	game.black = game.black.redacted();
+
+	Ok(tide::Body::from_json(&game)?.into())
+}
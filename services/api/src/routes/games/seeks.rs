@@ -0,0 +1,240 @@
+use super::{is_at_game_limit, CreateGameSide};
+use crate::{
+	hub::GameEventKind,
+	models::{
+		clock::TimeControl,
+		db,
+		game::{color_to_column, Game},
+		rating::{RatingCategory, UserRating, DEFAULT_RATING},
+		seek::Seek,
+		user::User,
+	},
+	webhooks, State,
+};
+use chess::Color;
+use serde::Deserialize;
+use sqlx::{types::Uuid, Postgres, Transaction};
+use std::convert::TryInto;
+use tide::{Request, Response, StatusCode};
+
+#[derive(Debug, Deserialize)]
+struct CreateSeek {
+	#[serde(default)]
+	side: CreateGameSide,
+	time_control: Option<TimeControl>,
+	#[serde(default)]
+	casual: bool,
+	rating_min: Option<i32>,
+	rating_max: Option<i32>,
+}
+
+/// Starts the game for `seek` against `opponent_id`, unless either player is at their game limit.
+async fn start_game(
+	txn: &mut Transaction<'_, Postgres>,
+	seek: &Seek,
+	opponent_id: Uuid,
+	opponent_color: Option<Color>,
+) -> tide::Result<Option<Game>> {
+	let seeker_color = seek
+		.color
+		.or_else(|| opponent_color.map(|color| !color))
+		.unwrap_or_else(|| Color::from(&CreateGameSide::Random));
+	let (white_id, black_id) = match seeker_color {
+		Color::White => (seek.user_id, opponent_id),
+		Color::Black => (opponent_id, seek.user_id),
+	};
+
+	if is_at_game_limit(&white_id, &black_id, &mut *txn).await? {
+		return Ok(None);
+	}
+
+	let game: Game = db::NewGame {
+		time_control: seek.time_control,
+		rated: seek.rated,
+		..db::NewGame::new(white_id, black_id)
+	}
+	.insert(&mut *txn)
+	.await?
+	.try_into()?;
+
+	sqlx::query!("update seeks set game_id = $1 where id = $2", game.id, seek.id)
+		.execute(&mut *txn)
+		.await?;
+
+	Ok(Some(game))
+}
+
+pub async fn create_seek(mut req: Request<State>) -> tide::Result {
+	let body: CreateSeek = req.body_json().await?;
+	let user = req.ext::<User>().unwrap();
+	let state = req.state();
+
+	let color = match body.side {
+		CreateGameSide::White => Some(Color::White),
+		CreateGameSide::Black => Some(Color::Black),
+		CreateGameSide::Random => None,
+	};
+	let (time_control, time_base_ms, time_increment_ms, days_per_move) = match &body.time_control {
+		Some(time_control) => {
+			let (name, base_ms, increment_ms, days) = time_control.to_columns();
+			(Some(name), base_ms, increment_ms, days)
+		}
+		None => (None, None, None, None),
+	};
+
+	let category = RatingCategory::from(body.time_control.as_ref());
+	let rating = UserRating::fetch(&user.id, category, &state.db).await?.rating;
+
+	let mut txn = state.db.begin().await?;
+
+	let mut seek: Seek = sqlx::query_as!(
+		db::Seek,
+		"insert into seeks (user_id, color, time_control, time_base_ms, time_increment_ms, days_per_move, rated, rating_min, rating_max)
+values ($1, $2, $3, $4, $5, $6, $7, $8, $9) returning *",
+		user.id,
+		color.map(color_to_column),
+		time_control,
+		time_base_ms,
+		time_increment_ms,
+		days_per_move,
+		!body.casual,
+		body.rating_min,
+		body.rating_max
+	)
+	.fetch_one(&mut txn)
+	.await?
+	.into();
+
+	// pair with the longest-waiting seek that both players fit
+	let candidate: Option<Seek> = sqlx::query_as!(
+		db::Seek,
+		"select seeks.* from seeks
+left join ratings on ratings.user_id = seeks.user_id and ratings.category = $7
+where seeks.game_id is null and seeks.user_id <> $1
+	and seeks.time_control is not distinct from $2 and seeks.time_base_ms is not distinct from $3
+	and seeks.time_increment_ms is not distinct from $4 and seeks.days_per_move is not distinct from $5
+	and (seeks.color is null or $6::varchar is null or seeks.color <> $6)
+	and seeks.rated = $8
+	and $9 between coalesce(seeks.rating_min, $9) and coalesce(seeks.rating_max, $9)
+	and coalesce(ratings.rating, $10) between coalesce($11, ratings.rating, $10) and coalesce($12, ratings.rating, $10)
+order by seeks.created_at
+limit 1
+for update of seeks skip locked",
+		user.id,
+		time_control,
+		time_base_ms,
+		time_increment_ms,
+		days_per_move,
+		color.map(color_to_column),
+		<&str>::from(category),
+		!body.casual,
+		rating.round() as i32,
+		DEFAULT_RATING,
+		body.rating_min.map(f64::from),
+		body.rating_max.map(f64::from)
+	)
+	.fetch_optional(&mut txn)
+	.await?
+	.map(Into::into);
+
+	let game = match candidate {
+		Some(candidate) => start_game(&mut txn, &candidate, user.id, color).await?,
+		None => None,
+	};
+
+	if let Some(game) = &game {
+		sqlx::query!("update seeks set game_id = $1 where id = $2", game.id, seek.id)
+			.execute(&mut txn)
+			.await?;
+		seek.game_id = Some(game.id);
+	}
+
+	let game = match game {
+		Some(game) => {
+			let game = game.with_users(&state.db).await?;
+			webhooks::enqueue(&game, &[GameEventKind::Created], &mut txn).await?;
+			Some(game)
+		}
+		None => None,
+	};
+
+	txn.commit().await?;
+
+	if let Some(game) = game {
+		state.publish(&game, &[GameEventKind::Created]).await;
+	}
+
+	Ok(tide::Body::from_json(&seek)?.into())
+}
+
+pub async fn list_seeks(req: Request<State>) -> tide::Result {
+	let pool = &req.state().db;
+
+	let seeks = sqlx::query_as!(
+		db::Seek,
+		"select * from seeks where game_id is null order by created_at"
+	)
+	.fetch_all(pool)
+	.await?
+	.into_iter()
+	.map(Seek::from)
+	.collect::<Vec<_>>();
+
+	Ok(tide::Body::from_json(&seeks)?.into())
+}
+
+pub async fn accept_seek(req: Request<State>) -> tide::Result {
+	let user = req.ext::<User>().unwrap();
+	let seek_id = req.param("seek_id")?.parse::<Uuid>()?;
+	let state = req.state();
+
+	let mut txn = state.db.begin().await?;
+
+	let seek: Seek = match sqlx::query_as!(
+		db::Seek,
+		"select * from seeks where id = $1 and game_id is null for update",
+		seek_id
+	)
+	.fetch_optional(&mut txn)
+	.await?
+	{
+		Some(seek) => seek.into(),
+		None => return Ok(Response::new(StatusCode::NotFound)),
+	};
+
+	if seek.user_id == user.id {
+		return Ok(tide::Error::from_str(StatusCode::BadRequest, "You can't accept your own seek").into());
+	}
+
+	let game = match start_game(&mut txn, &seek, user.id, None).await? {
+		Some(game) => game,
+		None => return Ok(tide::Error::from_str(StatusCode::BadRequest, "too many games in progress").into()),
+	};
+
+	let game = game.with_users(&state.db).await?;
+	webhooks::enqueue(&game, &[GameEventKind::Created], &mut txn).await?;
+	txn.commit().await?;
+
+	state.publish(&game, &[GameEventKind::Created]).await;
+	Ok(tide::Body::from_json(&game)?.into())
+}
+
+pub async fn cancel_seek(req: Request<State>) -> tide::Result {
+	let user = req.ext::<User>().unwrap();
+	let seek_id = req.param("seek_id")?.parse::<Uuid>()?;
+	let pool = &req.state().db;
+
+	let deleted = sqlx::query!(
+		"delete from seeks where id = $1 and user_id = $2 and game_id is null",
+		seek_id,
+		user.id
+	)
+	.execute(pool)
+	.await?
+	.rows_affected();
+
+	match deleted {
+		0 => Ok(Response::new(StatusCode::NotFound)),
+		_ => Ok(Response::new(StatusCode::NoContent)),
+	}
+}
@@ -0,0 +1,397 @@
+use crate::{
+	models::{
+		clock::TimeControl,
+		db,
+		game::{color_from_column, color_to_column, Game, GameError},
+		rating::{Glicko, RatingCategory},
+		stats::{Outcome, UserStats},
+		user::{AccountType, User, UserWithAccounts},
+	},
+	State,
+};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use std::convert::TryInto;
+use tide::{Request, Response, StatusCode};
+
+const DEFAULT_PAGE_SIZE: i64 = 25;
+const MAX_PAGE_SIZE: i64 = 100;
+const MAX_CONCURRENT_GAMES: i32 = 100;
+
+fn bad_request(message: impl Into<String>) -> tide::Error {
+	tide::Error::from_str(StatusCode::BadRequest, message.into())
+}
+
+#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum GameStatus {
+	InProgress,
+	Finished,
+}
+
+#[derive(Debug, Deserialize)]
+struct GameHistoryQuery {
+	/// The `next_cursor` of the previous page.
+	cursor: Option<String>,
+	limit: Option<i64>,
+	result: Option<Outcome>,
+	color: Option<String>,
+	#[serde(default, with = "crate::serde::option_uuid")]
+	opponent: Option<Uuid>,
+	since: Option<NaiveDateTime>,
+	until: Option<NaiveDateTime>,
+	status: Option<GameStatus>,
+}
+
+/// Pages are ordered by `modified_at`, with the game ID breaking ties between games modified in the same second.
+fn encode_cursor(modified_at: &NaiveDateTime, id: &Uuid) -> String {
+	format!("{}_{}", modified_at.timestamp(), id)
+}
+
+fn decode_cursor(cursor: &str) -> Option<(NaiveDateTime, Uuid)> {
+	let mut parts = cursor.splitn(2, '_');
+	let timestamp = parts.next()?.parse().ok()?;
+	let id = parts.next()?.parse().ok()?;
+
+	Some((NaiveDateTime::from_timestamp_opt(timestamp, 0)?, id))
+}
+
+#[derive(Debug, Serialize)]
+struct GameSummary {
+	#[serde(with = "crate::serde::uuid")]
+	id: Uuid,
+	#[serde(with = "crate::serde::uuid")]
+	white_id: Uuid,
+	#[serde(with = "crate::serde::uuid")]
+	black_id: Uuid,
+	result: Option<String>,
+	termination: Option<String>,
+	time_control: Option<TimeControl>,
+	rated: bool,
+	plies: usize,
+	/// The position after the last move.
+	fen: String,
+	created_at: NaiveDateTime,
+	modified_at: NaiveDateTime,
+}
+
+impl From<db::Game> for GameSummary {
+	fn from(game: db::Game) -> Self {
+		Self {
+			time_control: TimeControl::from_columns(
+				game.time_control.as_deref(),
+				game.time_base_ms,
+				game.time_increment_ms,
+				game.days_per_move,
+			),
+			plies: game.moves.len(),
+			id: game.id,
+			white_id: game.white_id,
+			black_id: game.black_id,
+			result: game.result,
+			termination: game.termination,
+			rated: game.rated,
+			fen: game.board,
+			created_at: game.created_at,
+			modified_at: game.modified_at,
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+struct GameHistoryPage {
+	games: Vec<GameSummary>,
+	next_cursor: Option<String>,
+}
+
+pub async fn list_games(req: Request<State>) -> tide::Result {
+	let user_id = req.param("user_id")?.parse::<Uuid>()?;
+	let query: GameHistoryQuery = req.query()?;
+	let viewer_id = req.ext::<User>().unwrap().id;
+	let delay = req.state().broadcast_delay_plies;
+	let pool = &req.state().db;
+
+	let color = match query.color.as_deref() {
+		Some(column) => {
+			let color = color_from_column(column).ok_or_else(|| bad_request(format!("invalid color: {}", column)))?;
+			Some(color_to_column(color))
+		}
+		None => None,
+	};
+	let (cursor_modified_at, cursor_id) = match query.cursor.as_deref() {
+		Some(cursor) => {
+			let (modified_at, id) = decode_cursor(cursor).ok_or_else(|| bad_request("invalid cursor"))?;
+			(Some(modified_at), Some(id))
+		}
+		None => (None, None),
+	};
+	let outcome = query.result.map(<&str>::from);
+	let finished = query.status.map(|status| status == GameStatus::Finished);
+	let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1).min(MAX_PAGE_SIZE);
+
+	// one extra game is fetched to tell whether there's another page
+	let mut games = sqlx::query_as!(
+		db::Game,
+		"select * from games
+where (white_id = $1 or black_id = $1)
+	and ($2::varchar is null or ($2 = 'White' and white_id = $1) or ($2 = 'Black' and black_id = $1))
+	and ($3::varchar is null
+		or ($3 = 'Win' and (
+			(white_id = $1 and result in ('WhiteCheckmates', 'BlackResigns'))
+			or (black_id = $1 and result in ('BlackCheckmates', 'WhiteResigns'))
+		))
+		or ($3 = 'Loss' and (
+			(white_id = $1 and result in ('BlackCheckmates', 'WhiteResigns'))
+			or (black_id = $1 and result in ('WhiteCheckmates', 'BlackResigns'))
+		))
+		or ($3 = 'Draw' and result in ('Stalemate', 'DrawAccepted', 'DrawDeclared')))
+	and ($4::uuid is null or (white_id = $1 and black_id = $4) or (black_id = $1 and white_id = $4))
+	and ($5::timestamp is null or created_at >= $5)
+	and ($6::timestamp is null or created_at < $6)
+	and ($7::bool is null or (result is not null) = $7)
+	and ($8::timestamp is null or (modified_at, id) < ($8, $9::uuid))
+order by modified_at desc, id desc
+limit $10",
+		user_id,
+		color,
+		outcome,
+		query.opponent,
+		query.since,
+		query.until,
+		finished,
+		cursor_modified_at,
+		cursor_id,
+		limit + 1
+	)
+	.fetch_all(pool)
+	.await?;
+
+	let next_cursor = if games.len() as i64 > limit {
+		games.truncate(limit as usize);
+		games.last().map(|game| encode_cursor(&game.modified_at, &game.id))
+	} else {
+		None
+	};
+
+	let games = games
+		.into_iter()
+		.map(|mut game| {
+			// spectators see games in progress a few plies behind
+			if game.result.is_none() && delay > 0 && viewer_id != game.white_id && viewer_id != game.black_id {
+				let mut delayed: Game = game.clone().try_into()?;
+				delayed.delay(delay)?;
+				game.board = delayed.board.current_position().to_string();
+				game.moves = delayed.moves;
+			}
+			Ok(GameSummary::from(game))
+		})
+		.collect::<Result<Vec<_>, GameError>>()?;
+
+	let page = GameHistoryPage {
+		games,
+		next_cursor,
+	};
+	Ok(tide::Body::from_json(&page)?.into())
+}
+
+pub async fn get_stats(req: Request<State>) -> tide::Result {
+	let user_id = req.param("user_id")?.parse::<Uuid>()?;
+	let pool = &req.state().db;
+
+	let games = sqlx::query_as!(
+		db::Game,
+		"select * from games where (white_id = $1 or black_id = $1) and result is not null order by modified_at desc",
+		user_id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	Ok(tide::Body::from_json(&UserStats::from_games(&user_id, &games))?.into())
+}
+
+#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum LeaderboardOrder {
+	Rating,
+	Wins,
+}
+
+impl Default for LeaderboardOrder {
+	fn default() -> Self {
+		LeaderboardOrder::Rating
+	}
+}
+
+#[derive(Debug, Deserialize)]
+struct LeaderboardQuery {
+	#[serde(default)]
+	by: LeaderboardOrder,
+	category: Option<RatingCategory>,
+	account_type: Option<AccountType>,
+	limit: Option<i64>,
+	#[serde(default)]
+	offset: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct LeaderboardEntry {
+	rank: i64,
+	user: UserWithAccounts,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	rating: Option<Glicko>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	wins: Option<i64>,
+}
+
+pub async fn get_leaderboard(req: Request<State>) -> tide::Result {
+	let query: LeaderboardQuery = req.query()?;
+	let pool = &req.state().db;
+
+	let account_type = query.account_type.as_ref().map(<&str>::from);
+	let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1).min(MAX_PAGE_SIZE);
+	let offset = query.offset.max(0);
+
+	let rows: Vec<(Uuid, Option<Glicko>, Option<i64>)> = match query.by {
+		LeaderboardOrder::Rating => {
+			let category: &str = query.category.unwrap_or(RatingCategory::Blitz).into();
+			sqlx::query!(
+				"select user_id, rating, deviation, volatility from ratings
+where category = $1 and ($2::varchar is null or exists (
+	select 1 from user_accounts where user_accounts.user_id = ratings.user_id and user_accounts.account_type = $2
+))
+order by rating desc, user_id
+limit $3 offset $4",
+				category,
+				account_type,
+				limit,
+				offset
+			)
+			.fetch_all(pool)
+			.await?
+			.into_iter()
+			.map(|row| {
+				let rating = Glicko {
+					rating: row.rating,
+					deviation: row.deviation,
+					volatility: row.volatility,
+				};
+				(row.user_id, Some(rating), None)
+			})
+			.collect()
+		}
+		LeaderboardOrder::Wins => sqlx::query!(
+			r#"select users.id, count(*) as "wins!" from users
+join games on games.white_id <> games.black_id and (
+	(games.white_id = users.id and games.result in ('WhiteCheckmates', 'BlackResigns'))
+	or (games.black_id = users.id and games.result in ('BlackCheckmates', 'WhiteResigns'))
+)
+where $1::varchar is null or exists (
+	select 1 from user_accounts where user_accounts.user_id = users.id and user_accounts.account_type = $1
+)
+group by users.id
+order by "wins!" desc, users.id
+limit $2 offset $3"#,
+			account_type,
+			limit,
+			offset
+		)
+		.fetch_all(pool)
+		.await?
+		.into_iter()
+		.map(|row| (row.id, None, Some(row.wins)))
+		.collect(),
+	};
+
+	let user_ids: Vec<Uuid> = rows.iter().map(|(user_id, _, _)| user_id.clone()).collect();
+	let users = UserWithAccounts::fetch_many(&user_ids, pool).await?;
+	let entries: Vec<LeaderboardEntry> = rows
+		.into_iter()
+		.zip(users)
+		.enumerate()
+		.map(|(rank, ((_, rating, wins), user))| LeaderboardEntry {
+			rank: offset + rank as i64 + 1,
+			user,
+			rating,
+			wins,
+		})
+		.collect();
+
+	Ok(tide::Body::from_json(&entries)?.into())
+}
+
+#[derive(Debug, Serialize)]
+struct Settings {
+	max_games: i32,
+	#[serde(with = "crate::serde::option_uuid")]
+	default_game_id: Option<Uuid>,
+}
+
+impl From<&User> for Settings {
+	fn from(user: &User) -> Self {
+		Self {
+			max_games: user.max_games,
+			default_game_id: user.default_game_id,
+		}
+	}
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateSettings {
+	max_games: Option<i32>,
+	/// A game in progress to use as the `default` game; `null` clears it.
+	#[serde(default, deserialize_with = "deserialize_present")]
+	default_game_id: Option<Option<Uuid>>,
+}
+
+/// Distinguishes a field set to `null` from one that's missing, which `#[serde(default)]` leaves as `None`.
+fn deserialize_present<'de, D>(d: D) -> Result<Option<Option<Uuid>>, D::Error>
+where
+	D: serde::Deserializer<'de>,
+{
+	crate::serde::option_uuid::deserialize(d).map(Some)
+}
+
+pub async fn get_settings(req: Request<State>) -> tide::Result {
+	let user = req.ext::<User>().unwrap();
+	Ok(tide::Body::from_json(&Settings::from(user))?.into())
+}
+
+pub async fn update_settings(mut req: Request<State>) -> tide::Result {
+	let body: UpdateSettings = req.body_json().await?;
+	let user = req.ext::<User>().unwrap();
+	let pool = &req.state().db;
+
+	let max_games = body.max_games.unwrap_or(user.max_games);
+	if max_games < 1 || max_games > MAX_CONCURRENT_GAMES {
+		return Ok(bad_request(format!("max_games must be between 1 and {}", MAX_CONCURRENT_GAMES)).into());
+	}
+
+	let default_game_id = body.default_game_id.unwrap_or(user.default_game_id);
+	if let Some(game_id) = default_game_id {
+		let playing = sqlx::query!(
+			"select id from games where id = $1 and (white_id = $2 or black_id = $2) and result is null",
+			game_id,
+			user.id
+		)
+		.fetch_optional(pool)
+		.await?
+		.is_some();
+
+		if !playing {
+			return Ok(Response::new(StatusCode::NotFound));
+		}
+	}
+
+	let user = sqlx::query_as!(
+		User,
+		"update users set max_games = $1, default_game_id = $2, modified_at = now() where id = $3 returning *",
+		max_games,
+		default_game_id,
+		user.id
+	)
+	.fetch_one(pool)
+	.await?;
+
+	Ok(tide::Body::from_json(&Settings::from(&user))?.into())
+}
@@ -0,0 +1,79 @@
+use crate::{
+	models::{user::User, webhook::Webhook},
+	webhooks, State,
+};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Deserialize;
+use sqlx::types::Uuid;
+use tide::{Request, Response, StatusCode};
+
+#[derive(Debug, Deserialize)]
+struct CreateWebhook {
+	url: String,
+}
+
+pub async fn create_webhook(mut req: Request<State>) -> tide::Result {
+	let body: CreateWebhook = req.body_json().await?;
+	let user = req.ext::<User>().unwrap();
+	let pool = &req.state().db;
+
+	let allowed = match tide::http::Url::parse(&body.url) {
+		Ok(url) => webhooks::is_allowed_url(&url).await,
+		Err(_) => false,
+	};
+	if !allowed {
+		return Ok(tide::Error::from_str(
+			StatusCode::BadRequest,
+			"url must be an http(s) URL on a public address",
+		)
+		.into());
+	}
+
+	let secret: String = rand::thread_rng().sample_iter(&Alphanumeric).take(32).collect();
+	let webhook = sqlx::query_as!(
+		Webhook,
+		"insert into webhooks (user_id, url, secret) values ($1, $2, $3) returning *",
+		user.id,
+		body.url,
+		secret
+	)
+	.fetch_one(pool)
+	.await?;
+
+	Ok(tide::Body::from_json(&webhook)?.into())
+}
+
+pub async fn list_webhooks(req: Request<State>) -> tide::Result {
+	let user = req.ext::<User>().unwrap();
+	let pool = &req.state().db;
+
+	let webhooks = sqlx::query_as!(
+		Webhook,
+		"select * from webhooks where user_id = $1 order by created_at",
+		user.id
+	)
+	.fetch_all(pool)
+	.await?;
+
+	Ok(tide::Body::from_json(&webhooks)?.into())
+}
+
+pub async fn delete_webhook(req: Request<State>) -> tide::Result {
+	let user = req.ext::<User>().unwrap();
+	let webhook_id = req.param("webhook_id")?.parse::<Uuid>()?;
+	let pool = &req.state().db;
+
+	let deleted = sqlx::query!(
+		"delete from webhooks where id = $1 and user_id = $2",
+		webhook_id,
+		user.id
+	)
+	.execute(pool)
+	.await?
+	.rows_affected();
+
+	match deleted {
+		0 => Ok(Response::new(StatusCode::NotFound)),
+		_ => Ok(Response::new(StatusCode::NoContent)),
+	}
+}
@@ -1 +1,2 @@
 pub mod uuid;
+pub mod option_uuid;
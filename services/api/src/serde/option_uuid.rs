@@ -0,0 +1,24 @@
+use serde::{
+	de::{Deserialize, Deserializer, Error},
+	ser::Serializer,
+};
+use sqlx::types::Uuid;
+
+pub fn serialize<S>(uuid: &Option<Uuid>, s: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+{
+	match uuid {
+		Some(uuid) => super::uuid::serialize(uuid, s),
+		None => s.serialize_none(),
+	}
+}
+
+pub fn deserialize<'de, D>(d: D) -> Result<Option<Uuid>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	Option::<String>::deserialize(d)?
+		.map(|uuid| Uuid::parse_str(&uuid).map_err(Error::custom))
+		.transpose()
+}
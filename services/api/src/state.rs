@@ -1,6 +1,36 @@
+use crate::{
+	amqp,
+	hub::{GameEventKind, Hub},
+	models::game::GameWithUsers,
+};
 use sqlx::postgres::PgPool;
+use std::time::Duration;
+use tide::log;
 
 #[derive(Debug, Clone)]
 pub struct State {
 	pub db: PgPool,
+	/// How many plies behind games in progress are shown on public routes.
+	pub broadcast_delay_plies: usize,
+	/// How long a challenge waits for its target to respond.
+	pub challenge_expiry: Duration,
+	pub hub: Hub,
+	pub amqp: Option<amqp::Publisher>,
+}
+
+impl State {
+	/// Tells everyone following the game what happened in it, once the change has been committed.
+	/// Webhooks aren't sent from here: their deliveries are queued with [`crate::webhooks::enqueue`] as part of the change.
+	/// Failures are only logged: the change has already been made, so the request that made it must still succeed.
+	pub async fn publish(&self, game: &GameWithUsers, kinds: &[GameEventKind]) {
+		if let Err(e) = self.hub.publish(game, kinds) {
+			log::error!("failed to publish game {} to subscribers: {}", game.id, e);
+		}
+
+		if let Some(amqp) = &self.amqp {
+			if let Err(e) = amqp.publish(game, kinds) {
+				log::error!("failed to publish game {} to AMQP: {}", game.id, e);
+			}
+		}
+	}
 }
@@ -0,0 +1,426 @@
+use crate::{hub::GameEventKind, models::game::GameWithUsers};
+use async_std::{
+	net::{TcpStream, ToSocketAddrs},
+	task,
+};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+use sqlx::{postgres::PgPool, types::Uuid, Postgres, Transaction};
+use std::{
+	net::{IpAddr, SocketAddr},
+	time::Duration,
+};
+use tide::{
+	http::{mime, Method, Request, Response, Url},
+	log, StatusCode,
+};
+
+/// Deliveries are abandoned after this many failed attempts.
+const MAX_ATTEMPTS: i32 = 8;
+/// The delay before the first retry; each later retry waits twice as long as the one before.
+const BASE_RETRY_SECS: f64 = 30.0;
+
+/// The `x-signature-256` header value for `payload`.
+pub fn sign(secret: &str, payload: &str) -> String {
+	let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC accepts keys of any length");
+	mac.update(payload.as_bytes());
+	format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Whether webhooks may be sent to `ip`. Loopback, private, link-local (which includes cloud metadata services) and
+/// other non-public addresses are refused, so webhooks can't be used to reach the API's own network.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+	match ip {
+		IpAddr::V4(ip) => {
+			let [a, b, ..] = ip.octets();
+			!(ip.is_loopback()
+				|| ip.is_private()
+				|| ip.is_link_local()
+				|| ip.is_unspecified()
+				|| ip.is_broadcast()
+				|| ip.is_multicast()
+				|| ip.is_documentation()
+				|| a == 0
+				// shared address space used for carrier-grade NAT
+				|| (a == 100 && (64..128).contains(&b)))
+		}
+		IpAddr::V6(ip) => {
+			if let Some(ip) = ip.to_ipv4() {
+				return is_public_ip(IpAddr::V4(ip));
+			}
+
+			let first = ip.segments()[0];
+			!(ip.is_loopback()
+				|| ip.is_unspecified()
+				|| ip.is_multicast()
+				// unique local addresses
+				|| first & 0xfe00 == 0xfc00
+				// link-local addresses
+				|| first & 0xffc0 == 0xfe80)
+		}
+	}
+}
+
+/// The address to send to for `url`, if it's an http(s) URL whose host only resolves to addresses `allow` accepts.
+async fn resolve(url: &Url, allow: fn(IpAddr) -> bool) -> Option<SocketAddr> {
+	if url.scheme() != "http" && url.scheme() != "https" {
+		return None;
+	}
+
+	let host = url.host_str()?.trim_start_matches('[').trim_end_matches(']');
+	let port = url.port_or_known_default()?;
+	let addrs = match host.parse::<IpAddr>() {
+		Ok(ip) => vec![SocketAddr::new(ip, port)],
+		Err(_) => (host, port).to_socket_addrs().await.ok()?.collect(),
+	};
+
+	if addrs.iter().all(|addr| allow(addr.ip())) {
+		addrs.into_iter().next()
+	} else {
+		None
+	}
+}
+
+/// Whether `url` is an http(s) URL whose host only resolves to public addresses.
+pub async fn is_allowed_url(url: &Url) -> bool {
+	resolve(url, is_public_ip).await.is_some()
+}
+
+/// Queues a delivery of each event to every webhook registered by either player.
+/// This belongs in the transaction that changes the game, so the deliveries are committed along with the change.
+pub async fn enqueue(
+	game: &GameWithUsers,
+	kinds: &[GameEventKind],
+	txn: &mut Transaction<'_, Postgres>,
+) -> tide::Result<()> {
+	for event in kinds.iter().filter_map(GameEventKind::topic) {
+		let payload = serde_json::json!({ "event": event, "game": game }).to_string();
+
+		sqlx::query!(
+			"insert into webhook_deliveries (webhook_id, event, payload)
+select id, $1, $2 from webhooks where user_id = $3 or user_id = $4",
+			event,
+			payload,
+			game.white.id,
+			game.black.id
+		)
+		.execute(&mut *txn)
+		.await?;
+	}
+
+	Ok(())
+}
+
+/// Periodically sends queued webhook deliveries, retrying failures with exponential backoff.
+#[derive(Debug, Clone)]
+pub struct Deliverer {
+	pub db: PgPool,
+	pub interval: Duration,
+	/// How long an endpoint has to respond before the attempt counts as failed.
+	pub timeout: Duration,
+}
+
+impl Deliverer {
+	pub async fn run(self) {
+		loop {
+			if let Err(e) = self.deliver_all().await {
+				log::error!("webhook delivery failed: {}", e);
+			}
+
+			task::sleep(self.interval).await;
+		}
+	}
+
+	async fn deliver_all(&self) -> tide::Result<()> {
+		let deliveries = sqlx::query!(
+			"select webhook_deliveries.id, webhook_deliveries.event, webhook_deliveries.payload, webhook_deliveries.attempts, webhooks.url, webhooks.secret
+from webhook_deliveries
+join webhooks on webhooks.id = webhook_deliveries.webhook_id
+where webhook_deliveries.delivered_at is null and webhook_deliveries.attempts < $1 and webhook_deliveries.next_attempt_at <= now()
+order by webhook_deliveries.next_attempt_at
+limit 100",
+			MAX_ATTEMPTS
+		)
+		.fetch_all(&self.db)
+		.await?;
+
+		for delivery in deliveries {
+			let outcome = send(
+				is_public_ip,
+				self.timeout,
+				&delivery.id,
+				&delivery.url,
+				&delivery.secret,
+				&delivery.event,
+				&delivery.payload,
+			)
+			.await;
+
+			match outcome {
+				Ok(()) => {
+					sqlx::query!(
+						"update webhook_deliveries set attempts = attempts + 1, delivered_at = now(), last_error = null where id = $1",
+						delivery.id
+					)
+					.execute(&self.db)
+					.await?;
+				}
+				Err(e) => {
+					let attempts = delivery.attempts + 1;
+					let delay = retry_delay(attempts);
+					if delay.is_none() {
+						log::warn!("giving up on webhook delivery {} after {} attempts: {}", delivery.id, attempts, e);
+					}
+
+					sqlx::query!(
+						"update webhook_deliveries set attempts = $1, next_attempt_at = coalesce(now() + $2 * interval '1 second', next_attempt_at), last_error = $3 where id = $4",
+						attempts,
+						delay.map(|delay| delay.as_secs_f64()),
+						e.to_string(),
+						delivery.id
+					)
+					.execute(&self.db)
+					.await?;
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// How long to wait before retrying a delivery that has failed `attempts` times, or `None` once it's abandoned.
+fn retry_delay(attempts: i32) -> Option<Duration> {
+	if attempts >= MAX_ATTEMPTS {
+		None
+	} else {
+		Some(Duration::from_secs_f64(BASE_RETRY_SECS * 2f64.powi(attempts - 1)))
+	}
+}
+
+/// Sends `request` over a new connection to `addr`, using TLS for https URLs.
+async fn post(addr: SocketAddr, request: Request) -> tide::Result<Response> {
+	let stream = TcpStream::connect(addr).await?;
+	if request.url().scheme() == "https" {
+		let host = request.url().host_str().unwrap_or_default().to_owned();
+		let stream = async_native_tls::connect(host.as_str(), stream).await?;
+		async_h1::connect(stream, request).await
+	} else {
+		async_h1::connect(stream, request).await
+	}
+}
+
+/// Makes a single attempt at delivering `payload`, which fails unless the endpoint responds with a success status.
+/// The host is resolved again for every attempt and only addresses `allow` accepts are used, since what it resolves to
+/// may have changed since the webhook was registered.
+async fn send(
+	allow: fn(IpAddr) -> bool,
+	timeout: Duration,
+	id: &Uuid,
+	url: &str,
+	secret: &str,
+	event: &str,
+	payload: &str,
+) -> tide::Result<()> {
+	let url: Url = url.parse()?;
+	// the request goes to the address that was checked rather than looking the host up again
+	let addr = resolve(&url, allow)
+		.await
+		.ok_or_else(|| tide::Error::from_str(StatusCode::Forbidden, "webhook URL doesn't resolve to a public address"))?;
+
+	let mut request = Request::new(Method::Post, url);
+	request.insert_header("x-webhook-id", id.to_string());
+	request.insert_header("x-webhook-event", event);
+	request.insert_header("x-signature-256", sign(secret, payload));
+	request.set_content_type(mime::JSON);
+	request.set_body(payload);
+
+	// deliveries are made one at a time, so a slow endpoint mustn't hold up everyone else's
+	let res = async_std::future::timeout(timeout, post(addr, request))
+		.await
+		.map_err(|_| tide::Error::from_str(StatusCode::GatewayTimeout, "webhook timed out"))??;
+
+	if res.status().is_success() {
+		Ok(())
+	} else {
+		Err(tide::Error::from_str(res.status(), format!("webhook responded with {}", res.status())))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use async_std::net::TcpListener;
+	use std::sync::{Arc, Mutex};
+
+	const SECRET: &str = "secret";
+	const PAYLOAD: &str = r#"{"event":"game.move"}"#;
+
+	#[derive(Debug, Clone)]
+	struct Received {
+		event: Option<String>,
+		signature: Option<String>,
+		body: String,
+	}
+
+	/// Starts a local endpoint that records every request and responds with `status` after `delay`.
+	async fn stand_in(status: StatusCode, delay: Duration) -> (String, Arc<Mutex<Vec<Received>>>) {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = format!("http://{}/hook", listener.local_addr().unwrap());
+		let received = Arc::new(Mutex::new(vec![]));
+
+		let mut app = tide::with_state(received.clone());
+		app.at("/hook").post(move |mut req: tide::Request<Arc<Mutex<Vec<Received>>>>| async move {
+			let body = req.body_string().await?;
+			req.state().lock().unwrap().push(Received {
+				event: req.header("x-webhook-event").map(|h| h.as_str().to_owned()),
+				signature: req.header("x-signature-256").map(|h| h.as_str().to_owned()),
+				body,
+			});
+
+			task::sleep(delay).await;
+			Ok(tide::Response::new(status))
+		});
+		task::spawn(app.listen(listener));
+
+		(url, received)
+	}
+
+	/// Sends to `url` as a delivery would, except that the local stand-in's address is allowed.
+	async fn send_to(url: &str) -> tide::Result<()> {
+		send(
+			|_| true,
+			Duration::from_secs(1),
+			&Uuid::nil(),
+			url,
+			SECRET,
+			"game.move",
+			PAYLOAD,
+		)
+		.await
+	}
+
+	#[test]
+	fn signs_with_hmac_sha256() {
+		assert_eq!(
+			sign("key", "The quick brown fox jumps over the lazy dog"),
+			"sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+		);
+	}
+
+	#[async_std::test]
+	async fn delivers_signed_payload() {
+		let (url, received) = stand_in(StatusCode::Ok, Duration::from_secs(0)).await;
+
+		send_to(&url).await.unwrap();
+
+		let received = received.lock().unwrap();
+		assert_eq!(received.len(), 1);
+		assert_eq!(received[0].event.as_deref(), Some("game.move"));
+		assert_eq!(received[0].body, PAYLOAD);
+
+		let signature = received[0].signature.as_deref().unwrap();
+		let mut mac = Hmac::<Sha256>::new_varkey(SECRET.as_bytes()).unwrap();
+		mac.update(PAYLOAD.as_bytes());
+		let expected = hex::decode(signature.strip_prefix("sha256=").unwrap()).unwrap();
+		assert!(mac.verify(&expected).is_ok());
+	}
+
+	#[async_std::test]
+	async fn fails_on_error_status() {
+		let (url, received) = stand_in(StatusCode::InternalServerError, Duration::from_secs(0)).await;
+
+		let err = send_to(&url).await.unwrap_err();
+		assert_eq!(err.status(), StatusCode::InternalServerError);
+		assert_eq!(received.lock().unwrap().len(), 1);
+	}
+
+	#[async_std::test]
+	async fn fails_on_timeout() {
+		let (url, _) = stand_in(StatusCode::Ok, Duration::from_secs(5)).await;
+
+		let err = send_to(&url).await.unwrap_err();
+		assert_eq!(err.status(), StatusCode::GatewayTimeout);
+	}
+
+	#[test]
+	fn backs_off_exponentially() {
+		assert_eq!(retry_delay(1), Some(Duration::from_secs(30)));
+		assert_eq!(retry_delay(2), Some(Duration::from_secs(60)));
+		assert_eq!(retry_delay(3), Some(Duration::from_secs(120)));
+		assert_eq!(retry_delay(MAX_ATTEMPTS - 1), Some(Duration::from_secs(30 * 64)));
+	}
+
+	#[async_std::test]
+	async fn gives_up_after_max_attempts() {
+		let (url, received) = stand_in(StatusCode::ServiceUnavailable, Duration::from_secs(0)).await;
+
+		let mut attempts = 0;
+		loop {
+			assert!(send_to(&url).await.is_err());
+			attempts += 1;
+
+			if retry_delay(attempts).is_none() {
+				break;
+			}
+		}
+
+		assert_eq!(attempts, MAX_ATTEMPTS);
+		assert_eq!(received.lock().unwrap().len(), MAX_ATTEMPTS as usize);
+	}
+
+	#[test]
+	fn refuses_non_public_addresses() {
+		for ip in &[
+			"127.0.0.1",
+			"10.1.2.3",
+			"172.16.0.1",
+			"192.168.1.1",
+			"169.254.169.254",
+			"100.64.0.1",
+			"0.0.0.0",
+			"::1",
+			"::ffff:127.0.0.1",
+			"fd00::1",
+			"fe80::1",
+		] {
+			assert!(!is_public_ip(ip.parse().unwrap()), "{} should be refused", ip);
+		}
+
+		for ip in &["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"] {
+			assert!(is_public_ip(ip.parse().unwrap()), "{} should be allowed", ip);
+		}
+	}
+
+	#[async_std::test]
+	async fn refuses_to_deliver_to_non_public_addresses() {
+		let (url, received) = stand_in(StatusCode::Ok, Duration::from_secs(0)).await;
+
+		let err = send(is_public_ip, Duration::from_secs(1), &Uuid::nil(), &url, SECRET, "game.move", PAYLOAD)
+			.await
+			.unwrap_err();
+		assert_eq!(err.status(), StatusCode::Forbidden);
+		assert!(received.lock().unwrap().is_empty());
+
+		// a hostname is resolved on every delivery, so one that now points at the local network is refused too
+		let by_name = url.replace("127.0.0.1", "localhost");
+		assert!(send(is_public_ip, Duration::from_secs(1), &Uuid::nil(), &by_name, SECRET, "game.move", PAYLOAD)
+			.await
+			.is_err());
+		assert!(received.lock().unwrap().is_empty());
+	}
+
+	#[async_std::test]
+	async fn refuses_urls_on_non_public_addresses() {
+		for url in &[
+			"http://127.0.0.1:8080/hook",
+			"http://169.254.169.254/latest",
+			"http://[::1]/",
+			"ftp://93.184.216.34/",
+		] {
+			assert!(!is_allowed_url(&url.parse().unwrap()).await, "{} should be refused", url);
+		}
+
+		assert!(is_allowed_url(&"https://93.184.216.34/hook".parse().unwrap()).await);
+	}
+}
@@ -37,33 +37,55 @@ interface Game {
 	side_to_move: 'White' | 'Black';
 	moves: string[];
 	result: string | null;
+	termination: string | null;
+}
+
+/** Why a game ended; a result of resigning or a declared draw also covers flags falling and abandoned games. */
+const terminations: Record<string, string> = {
+	Checkmate: 'by checkmate',
+	Resignation: 'by resignation',
+	Stalemate: 'by stalemate',
+	DrawAgreed: 'by agreement',
+	DrawClaimed: 'by claim',
+	ThreefoldRepetition: 'by threefold repetition',
+	FivefoldRepetition: 'by fivefold repetition',
+	FiftyMoveRule: 'by the fifty-move rule',
+	SeventyFiveMoveRule: 'by the seventy-five-move rule',
+	InsufficientMaterial: 'by insufficient material',
+	Timeout: 'on time',
+	Abandoned: 'by abandonment',
+};
+
+interface Challenge {
+	id: string;
+	challenger_id: string;
+	target_id: string;
 }
 
 function respondToGame(interaction: Interaction, game: Game) {
 	const userToMove = game[game.side_to_move.toLowerCase() as 'white' | 'black'].accounts.find(account => account.account_type === 'Discord')?.account_id;
 	const sideToMove = game.side_to_move.toLowerCase();
 
+	const reason = game.termination ? terminations[game.termination] : undefined;
+	const ending = reason ? ` ${reason}` : '';
+
 	let winnerId: string | undefined;
 	let content: string;
 	switch (game.result) {
 		case 'BlackCheckmates':
 		case 'WhiteResigns':
 			winnerId = game.black.accounts.find(account => account.account_type === 'Discord')?.account_id;
-			content = `<@${winnerId}> (black) wins!`;
+			content = `<@${winnerId}> (black) wins${ending}!`;
 			break;
 		case 'WhiteCheckmates':
 		case 'BlackResigns':
 			winnerId = game.white.accounts.find(account => account.account_type === 'Discord')?.account_id;
-			content = `<@${winnerId}> (white) wins!`;
+			content = `<@${winnerId}> (white) wins${ending}!`;
 			break;
 		case 'Stalemate':
-			content = 'Stalemate!';
-			break;
 		case 'DrawAccepted':
-			content = 'Draw accepted.';
-			break;
 		case 'DrawDeclared':
-			content = 'Draw declared.';
+			content = `Drawn${ending}.`;
 			break;
 		default:
 			content = `<@${userToMove}> (${sideToMove}) to move ${encodeURI(`${boardsUrl}/${game.board}`)}`;
@@ -94,29 +116,128 @@ broker.on('INTERACTION_CREATE', async (interaction: Interaction, { ack }: AmqpRe
 					break;
 				}
 				case 'challenge': {
-					const res = await fetch(`${apiUrl}/games`, {
+					const targetId = interaction.data?.options[0].value;
+					const res = await fetch(`${apiUrl}/challenges`, {
 						method: 'post',
 						headers: {
 							'x-user-id': interaction.member.user.id,
 							'x-account-type': 'Discord',
 						},
 						body: JSON.stringify({
-							target_id: interaction.data?.options[0].value,
+							target_id: targetId,
 							account_type: 'Discord',
 						}),
 					});
 
 					if (!res.ok) {
-						respond(interaction, 'can\'t create game');
+						respond(interaction, 'can\'t create challenge');
+						return;
+					}
+
+					respond(interaction, `<@${targetId}>, <@${interaction.member.user.id}> has challenged you! Use /accept to play or /decline to refuse.`);
+					break;
+				}
+				case 'accept':
+				case 'decline': {
+					const headers = {
+						'x-user-id': interaction.member.user.id,
+						'x-account-type': 'Discord',
+					};
+
+					const listRes = await fetch(`${apiUrl}/challenges?direction=incoming`, {
+						method: 'get',
+						headers,
+					});
+					const challenges: Challenge[] = listRes.ok ? await listRes.json() : [];
+					const challenge = challenges[challenges.length - 1];
+
+					if (!challenge) {
+						respond(interaction, 'no challenges');
+						return;
+					}
+
+					const res = await fetch(`${apiUrl}/challenges/${challenge.id}/${interaction.data?.name}`, {
+						method: 'post',
+						headers,
+					});
+
+					if (!res.ok) {
+						respond(interaction, `can't ${interaction.data?.name} challenge`);
+						return;
+					}
+
+					if (interaction.data?.name === 'accept') {
+						const game = await res.json();
+						respondToGame(interaction, game);
+					} else {
+						respond(interaction, 'challenge declined');
+					}
+					break;
+				}
+				case 'games': {
+					const res = await fetch(`${apiUrl}/games/active`, {
+						method: 'get',
+						headers: {
+							'x-user-id': interaction.member.user.id,
+							'x-account-type': 'Discord',
+						},
+					});
+
+					if (!res.ok) {
+						respond(interaction, 'unable to get games');
+						return;
+					}
+
+					const games: Game[] = await res.json();
+					if (!games.length) {
+						respond(interaction, 'no games in progress');
+						return;
+					}
+
+					const lines = games.map((game, i) => {
+						const white = game.white.accounts.find(account => account.account_type === 'Discord')?.account_id;
+						const black = game.black.accounts.find(account => account.account_type === 'Discord')?.account_id;
+						return `${i + 1}. <@${white}> (white) vs <@${black}> (black)`;
+					});
+					respond(interaction, `${lines.join('\n')}\nUse /default to choose which game your commands apply to.`);
+					break;
+				}
+				case 'default': {
+					const headers = {
+						'x-user-id': interaction.member.user.id,
+						'x-account-type': 'Discord',
+					};
+
+					const listRes = await fetch(`${apiUrl}/games/active`, {
+						method: 'get',
+						headers,
+					});
+					const games: Game[] = listRes.ok ? await listRes.json() : [];
+					const game = games[Number(interaction.data?.options[0].value) - 1];
+
+					if (!game) {
+						respond(interaction, 'no such game; use /games to list them');
+						return;
+					}
+
+					const res = await fetch(`${apiUrl}/settings`, {
+						method: 'patch',
+						headers,
+						body: JSON.stringify({
+							default_game_id: game.id,
+						}),
+					});
+
+					if (!res.ok) {
+						respond(interaction, 'unable to set default game');
 						return;
 					}
 
-					const game = await res.json();
 					respondToGame(interaction, game);
 					break;
 				}
 				case 'game': {
-					const res = await fetch(`${apiUrl}/games/current`, {
+					const res = await fetch(`${apiUrl}/games/default`, {
 						method: 'get',
 						headers: {
 							'x-user-id': interaction.member.user.id,
@@ -124,6 +245,11 @@ broker.on('INTERACTION_CREATE', async (interaction: Interaction, { ack }: AmqpRe
 						},
 					});
 
+					if (res.status === 409) {
+						respond(interaction, 'you have several games in progress; use /games and /default to choose one');
+						return;
+					}
+
 					if (!res.ok) {
 						respond(interaction, 'no game');
 						return;
@@ -135,7 +261,7 @@ broker.on('INTERACTION_CREATE', async (interaction: Interaction, { ack }: AmqpRe
 					break;
 				}
 				case 'move': {
-					const res = await fetch(`${apiUrl}/games/current/moves`, {
+					const res = await fetch(`${apiUrl}/games/default/moves`, {
 						method: 'put',
 						headers: {
 							'x-user-id': interaction.member.user.id,
@@ -157,7 +283,7 @@ broker.on('INTERACTION_CREATE', async (interaction: Interaction, { ack }: AmqpRe
 					break;
 				}
 				case 'resign': {
-					const res = await fetch(`${apiUrl}/games/current/moves`, {
+					const res = await fetch(`${apiUrl}/games/default/moves`, {
 						method: 'put',
 						headers: {
 							'x-user-id': interaction.member.user.id,